@@ -0,0 +1,151 @@
+use alloc::collections::{vec_deque, VecDeque};
+
+/// The most events a client can have waiting, matching the official sysmodule.
+pub const MAX_CLIENT_EVENT_COUNT: usize = 64;
+
+/// Each documented event type (1 through 9) has a mask bit, starting with type 1 at bit 0.
+pub const VALID_NOTIFICATION_MASK: u32 = 0x1ff;
+
+/// A queue that drops its oldest events when it's full,
+/// so a client that never polls can't grow it without limit.
+pub struct EventQueue<T> {
+    events: VecDeque<T>,
+    capacity: usize,
+    has_dropped_events: bool,
+}
+
+impl<T> EventQueue<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            events: VecDeque::with_capacity(capacity),
+            capacity,
+            has_dropped_events: false,
+        }
+    }
+
+    pub fn push(&mut self, event: T) {
+        if self.capacity == 0 {
+            self.has_dropped_events = true;
+            return;
+        }
+
+        if self.events.len() >= self.capacity {
+            self.events.pop_front();
+            self.has_dropped_events = true;
+        }
+
+        self.events.push_back(event);
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Removes up to `count` of the oldest events, in the order they were pushed.
+    pub fn drain(&mut self, count: usize) -> vec_deque::Drain<'_, T> {
+        let count = count.min(self.events.len());
+        self.events.drain(..count)
+    }
+
+    /// Drops every waiting event, along with the record of any dropped before.
+    pub fn clear(&mut self) {
+        self.events.clear();
+        self.has_dropped_events = false;
+    }
+
+    /// Returns whether events were dropped since the last call.
+    pub fn take_dropped_flag(&mut self) -> bool {
+        let has_dropped_events = self.has_dropped_events;
+        self.has_dropped_events = false;
+        has_dropped_events
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec::Vec;
+
+    mod push {
+        use super::*;
+
+        #[test]
+        fn should_keep_events_in_order() {
+            let mut queue = EventQueue::new(4);
+            queue.push(1);
+            queue.push(2);
+            queue.push(3);
+
+            let events: Vec<u32> = queue.drain(3).collect();
+            assert_eq!(events, [1, 2, 3]);
+            assert!(!queue.take_dropped_flag());
+        }
+
+        #[test]
+        fn should_drop_the_oldest_event_when_full() {
+            let mut queue = EventQueue::new(2);
+            queue.push(1);
+            queue.push(2);
+            queue.push(3);
+
+            let events: Vec<u32> = queue.drain(2).collect();
+            assert_eq!(events, [2, 3]);
+        }
+
+        #[test]
+        fn should_set_the_dropped_flag_on_overflow() {
+            let mut queue = EventQueue::new(1);
+            queue.push(1);
+            queue.push(2);
+
+            assert!(queue.take_dropped_flag());
+            assert!(!queue.take_dropped_flag());
+        }
+    }
+
+    mod drain {
+        use super::*;
+
+        #[test]
+        fn should_leave_events_that_were_not_drained() {
+            let mut queue = EventQueue::new(MAX_CLIENT_EVENT_COUNT);
+            queue.push(1);
+            queue.push(2);
+            queue.push(3);
+
+            let events: Vec<u32> = queue.drain(1).collect();
+            assert_eq!(events, [1]);
+            assert_eq!(queue.len(), 2);
+        }
+
+        #[test]
+        fn should_not_panic_when_draining_more_than_the_queue_has() {
+            let mut queue = EventQueue::new(4);
+            queue.push(1);
+
+            let events: Vec<u32> = queue.drain(10).collect();
+            assert_eq!(events, [1]);
+            assert!(queue.is_empty());
+        }
+    }
+
+    mod clear {
+        use super::*;
+
+        #[test]
+        fn should_drop_all_events_and_the_dropped_flag() {
+            let mut queue = EventQueue::new(1);
+            queue.push(1);
+            queue.push(2);
+
+            queue.clear();
+
+            assert!(queue.is_empty());
+            assert!(!queue.take_dropped_flag());
+        }
+    }
+}
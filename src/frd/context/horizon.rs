@@ -0,0 +1,117 @@
+use super::FriendServiceContext;
+use crate::{
+    frd::{
+        online_play::retry::DEFAULT_NASC_ATTEMPT_COUNT,
+        save::{
+            account::{AccountConfig, ACCOUNT_FILE_SIZE},
+            archive::{atomic_write, recover_interrupted_write, SaveArchive},
+            friend_list::{read_friend_list, MAX_FRIEND_COUNT},
+            health::validate_saves,
+            my_data::{MyData, MY_DATA_FILE_SIZE},
+            path::{get_save_file_path, SaveFile},
+        },
+        wifi::WiFiConnectionStatus,
+    },
+    log,
+};
+use alloc::{format, string::String, vec};
+use ctr::{
+    fs::{ArchiveId, FsArchive, FsPath, OpenFlags},
+    result::CtrResult,
+    svc,
+    svc::EventResetType,
+};
+
+// TODO: Don't assume the user is using account 1
+const LOCAL_ACCOUNT_ID: u32 = 1;
+
+fn get_path(save_file: SaveFile) -> CtrResult<String> {
+    Ok(get_save_file_path(LOCAL_ACCOUNT_ID, save_file)?)
+}
+
+fn open_save_archive() -> CtrResult<FsArchive> {
+    let save_archive_path = FsPath::new_binary([0, 0x10032]);
+    FsArchive::new(ArchiveId::SystemSaveData, &save_archive_path)
+}
+
+fn get_my_account(archive: &FsArchive) -> CtrResult<AccountConfig> {
+    let account_file = archive
+        .open_file(
+            &get_path(SaveFile::Account)?.as_str().into(),
+            OpenFlags::Read,
+        )?
+        .read(0, ACCOUNT_FILE_SIZE)?;
+
+    AccountConfig::try_from_le_bytes(&account_file)
+}
+
+fn get_my_data(archive: &FsArchive) -> CtrResult<MyData> {
+    let my_data_file = archive
+        .open_file(
+            &get_path(SaveFile::MyData)?.as_str().into(),
+            OpenFlags::Read,
+        )?
+        .read(0, MY_DATA_FILE_SIZE)?;
+
+    MyData::try_from_le_bytes(&my_data_file)
+}
+
+impl FriendServiceContext {
+    pub fn new() -> CtrResult<Self> {
+        let ndm_wifi_event_handle = svc::create_event(EventResetType::OneShot)?;
+
+        let mut archive = open_save_archive()?;
+
+        for save_file in [SaveFile::Account, SaveFile::MyData, SaveFile::FriendList] {
+            recover_interrupted_write(&mut archive, &get_path(save_file)?)?;
+        }
+
+        let save_health = validate_saves(&archive, LOCAL_ACCOUNT_ID);
+        if !save_health.is_healthy() {
+            log::debug(&format!("Unhealthy save files: {:?}", save_health));
+        }
+
+        // A fresh console has no friend list yet, which is the same as having no friends
+        let friend_list_path = get_path(SaveFile::FriendList)?;
+        let friend_list = if archive.exists(&friend_list_path) {
+            read_friend_list(&archive, &friend_list_path)?
+        } else {
+            vec![]
+        };
+
+        Ok(Self {
+            ndm_wifi_event_handle,
+            ndm_wifi_state: 0,
+            wifi_connection_status: WiFiConnectionStatus::Idle,
+            wifi_state_before_change: None,
+            counter: 0,
+            nasc_attempt_count: DEFAULT_NASC_ATTEMPT_COUNT,
+            logged_in: false,
+            friend_list,
+            account_config: get_my_account(&archive)?,
+            my_data: get_my_data(&archive)?,
+            my_online_activity: Default::default(),
+            online_friends: vec![],
+            nat_properties: Default::default(),
+            has_detected_nat_properties: false,
+            session_contexts: vec![],
+            friend_key_list: [Default::default(); MAX_FRIEND_COUNT],
+        })
+    }
+
+    pub fn reload_friend_list(&mut self) -> CtrResult<()> {
+        let archive = open_save_archive()?;
+        self.reload_friend_list_from(&archive, &get_path(SaveFile::FriendList)?)
+    }
+
+    pub fn save_my_data(&mut self) -> CtrResult<()> {
+        let mut archive = open_save_archive()?;
+        self.save_my_data_to(&mut archive, &get_path(SaveFile::MyData)?)
+    }
+
+    /// Replaces a save file without risking a partially written file.
+    pub fn atomic_write(&mut self, path: &str, data: &[u8]) -> CtrResult<()> {
+        let mut archive = open_save_archive()?;
+        atomic_write(&mut archive, path, data)
+    }
+}
@@ -0,0 +1,206 @@
+use super::FriendServiceContext;
+use crate::frd::{
+    online_play::retry::DEFAULT_NASC_ATTEMPT_COUNT,
+    save::{
+        account::{AccountConfig, NascEnvironment},
+        archive::{atomic_write, MemoryArchive},
+        friend_list::{
+            FriendEntry, FriendListHeader, FRIEND_LIST_HEADER_SIZE, FRIEND_LIST_MAGIC,
+            MAX_FRIEND_COUNT,
+        },
+        my_data::MyData,
+        path::{get_save_file_path, SaveFile},
+    },
+    wifi::WiFiConnectionStatus,
+};
+use alloc::{
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::mem;
+use ctr::{
+    frd::{FriendKey, FriendProfile, GameKey},
+    result::CtrResult,
+    time::FormattedTimestamp,
+};
+use no_std_io::Writer;
+
+pub const MOCK_PRINCIPAL_ID: u32 = 0xaabbccdd;
+pub const MOCK_LOCAL_FRIEND_CODE: u64 = 0x38aabbccdd;
+
+pub const MOCK_FRIEND_KEY: FriendKey = FriendKey {
+    principal_id: 0x11223344,
+    padding: 0,
+    local_friend_code: 0x0011223344,
+};
+
+pub fn create_mock_friend_last_online() -> FormattedTimestamp {
+    FormattedTimestamp::new(2021, 6, 15, 12, 30, 0)
+}
+
+fn create_mock_account_config() -> AccountConfig {
+    AccountConfig {
+        local_account_id: 1,
+        principal_id: MOCK_PRINCIPAL_ID,
+        local_friend_code: MOCK_LOCAL_FRIEND_CODE,
+        nex_password: "password".to_string(),
+        principal_id_hmac: "0123abcd".to_string(),
+        nasc_environment: NascEnvironment::Prod,
+        server_type_1: 2,
+        server_type_2: 2,
+    }
+}
+
+fn create_mock_my_data() -> MyData {
+    MyData {
+        my_nc_principal_id: 0,
+        changed_bit_flags: 0,
+        is_public_mode: true,
+        is_show_game_mode: true,
+        is_show_played_game: true,
+        my_favorite_game: GameKey::default(),
+        personal_comment: "Hello".to_string(),
+        profile: FriendProfile {
+            region: 1,
+            country: 49,
+            area: 2,
+            language: 1,
+            platform: 2,
+            padding: [0; 3],
+        },
+        mac_address: "0123456789AB".to_string(),
+        console_serial_number: "CW123456789".to_string(),
+        screen_name: "Mock".to_string(),
+        mii: Default::default(),
+    }
+}
+
+fn create_mock_friend() -> FriendEntry {
+    FriendEntry {
+        friend_key: MOCK_FRIEND_KEY,
+        friend_relationship: 3,
+        last_online: create_mock_friend_last_online(),
+        ..Default::default()
+    }
+}
+
+pub fn get_mock_save_file_path(save_file: SaveFile) -> String {
+    get_save_file_path(1, save_file).unwrap()
+}
+
+fn create_mock_friend_list_bytes() -> Vec<u8> {
+    let header = FriendListHeader {
+        magic: FRIEND_LIST_MAGIC,
+        ..Default::default()
+    };
+    let mut bytes = vec![];
+    bytes.checked_write_le(0, &header);
+    bytes.checked_write_le(FRIEND_LIST_HEADER_SIZE, &create_mock_friend());
+    bytes
+}
+
+fn create_mock_save_archive() -> MemoryArchive {
+    let mut save_archive = MemoryArchive::default();
+    save_archive.files.insert(
+        get_mock_save_file_path(SaveFile::FriendList),
+        create_mock_friend_list_bytes(),
+    );
+    save_archive.files.insert(
+        get_mock_save_file_path(SaveFile::MyData),
+        create_mock_my_data().to_le_bytes(&[]).unwrap(),
+    );
+    save_archive
+}
+
+/// A context with a hardcoded account and friend list so handlers can be tested on the host.
+///
+/// Saves go to an archive in memory, so anything written can be read back.
+impl FriendServiceContext {
+    pub fn new() -> CtrResult<Self> {
+        Ok(Self {
+            ndm_wifi_event_handle: 0.into(),
+            ndm_wifi_state: 0,
+            wifi_connection_status: WiFiConnectionStatus::Idle,
+            wifi_state_before_change: None,
+            counter: 0,
+            nasc_attempt_count: DEFAULT_NASC_ATTEMPT_COUNT,
+            logged_in: false,
+            friend_list: vec![create_mock_friend()],
+            account_config: create_mock_account_config(),
+            my_data: create_mock_my_data(),
+            my_online_activity: Default::default(),
+            online_friends: vec![],
+            nat_properties: Default::default(),
+            has_detected_nat_properties: false,
+            session_contexts: vec![],
+            save_archive: create_mock_save_archive(),
+            friend_key_list: [Default::default(); MAX_FRIEND_COUNT],
+        })
+    }
+
+    pub fn reload_friend_list(&mut self) -> CtrResult<()> {
+        let save_archive = mem::take(&mut self.save_archive);
+        let result = self.reload_friend_list_from(
+            &save_archive,
+            &get_mock_save_file_path(SaveFile::FriendList),
+        );
+        self.save_archive = save_archive;
+        result
+    }
+
+    pub fn save_my_data(&mut self) -> CtrResult<()> {
+        let mut save_archive = mem::take(&mut self.save_archive);
+        let result = self.save_my_data_to(
+            &mut save_archive,
+            &get_mock_save_file_path(SaveFile::MyData),
+        );
+        self.save_archive = save_archive;
+        result
+    }
+
+    pub fn atomic_write(&mut self, path: &str, data: &[u8]) -> CtrResult<()> {
+        atomic_write(&mut self.save_archive, path, data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    mod reload_friend_list {
+        use super::*;
+
+        #[test]
+        fn should_replace_friends_added_in_memory() {
+            let mut context = FriendServiceContext::new().unwrap();
+            let friend_key = FriendKey {
+                principal_id: 0x55667788,
+                padding: 0,
+                local_friend_code: 0,
+            };
+            context.add_friend(friend_key).unwrap();
+
+            context.reload_friend_list().expect("Should have reloaded");
+
+            assert_eq!(context.get_friend_keys(), &[MOCK_FRIEND_KEY]);
+        }
+    }
+
+    mod atomic_write {
+        use super::*;
+        use crate::frd::save::archive::SaveArchive;
+
+        #[test]
+        fn should_write_to_the_memory_archive() {
+            let mut context = FriendServiceContext::new().unwrap();
+
+            context
+                .atomic_write("/1/mydata", &[1, 2, 3])
+                .expect("Should have written");
+
+            let result = context.save_archive.read("/1/mydata", 0, 3);
+            assert_eq!(result, Ok(vec![1, 2, 3]));
+        }
+    }
+}
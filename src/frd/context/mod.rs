@@ -0,0 +1,1517 @@
+mod event_queue;
+pub use event_queue::*;
+
+mod notification_event;
+pub use notification_event::*;
+
+#[cfg(target_os = "horizon")]
+mod horizon;
+#[cfg(not(target_os = "horizon"))]
+pub mod mock;
+
+#[cfg(not(target_os = "horizon"))]
+use crate::frd::save::archive::MemoryArchive;
+use crate::{
+    frd::{
+        online_play::{
+            authentication::GameAuthenticationData, cache::CachedResponse,
+            locate::ServiceLocateData,
+        },
+        result::FrdErrorCode,
+        save::{
+            account::AccountConfig,
+            archive::{atomic_write, SaveArchive},
+            friend_list::{read_friend_list, FriendEntry, MAX_FRIEND_COUNT},
+            my_data::{merge_profile_update, validate_profile, MyData, MY_DATA_FILE_SIZE},
+        },
+        utils::{
+            convert_friend_code_to_principal_id, truncate_comment, validate_friend_code,
+            FriendKeyMatch,
+        },
+        wifi::WiFiConnectionStatus,
+    },
+    log,
+};
+use alloc::{format, vec, vec::Vec};
+use core::{cmp::min, mem};
+use ctr::{
+    frd::{
+        ExpandedFriendPresence, FriendInfo, FriendKey, FriendPresence, FriendProfile, GameKey,
+        NatProperties, NotificationEvent,
+    },
+    result::CtrResult,
+    svc,
+    time::SystemTimestamp,
+    Handle,
+};
+use no_std_io::{EndianRead, EndianWrite, Reader, StreamContainer, StreamWriter, Writer};
+
+/// Which client a session belongs to, for debugging.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EndianRead, EndianWrite)]
+pub struct SessionClientInfo {
+    pub process_id: u32,
+    pub client_sdk_version: u32,
+}
+
+/// The presence starts with the 0x2c byte game mode,
+/// followed by a UTF-16 game mode description of up to 0x80 characters.
+pub const GAME_MODE_DESCRIPTION_OFFSET: usize = 0x2c;
+pub const GAME_MODE_DESCRIPTION_SIZE: usize = 0x100;
+
+#[derive(Default)]
+pub struct OnlineActivity {
+    pub playing_game: GameKey,
+    pub presence: ExpandedFriendPresence,
+}
+
+/// Enough for a full friend key list, the most common large response,
+/// so steady state calls don't reallocate on the small sysmodule heap.
+/// Larger responses still grow the buffer, and the buffer keeps that capacity.
+pub const STATIC_BUFFER_RESERVE_SIZE: usize = MAX_FRIEND_COUNT * mem::size_of::<FriendKey>();
+
+pub struct SessionContext {
+    pub last_game_authentication_response: Option<CachedResponse<GameAuthenticationData>>,
+    pub last_service_locator_response: Option<CachedResponse<ServiceLocateData>>,
+    pub static_buffer: Vec<u8>,
+    pub process_id: u32,
+    pub client_sdk_version: u32,
+    pub notification_mask: u32,
+    pub server_time_interval: i64,
+    pub last_response_result: Result<(), FrdErrorCode>,
+    pub client_event: Option<Handle>,
+    pub client_event_queue: EventQueue<NotificationEvent>,
+}
+
+impl SessionContext {
+    pub fn new() -> Self {
+        Self {
+            last_game_authentication_response: None,
+            last_service_locator_response: None,
+            static_buffer: Vec::with_capacity(STATIC_BUFFER_RESERVE_SIZE),
+            process_id: 0,
+            client_sdk_version: 0,
+            notification_mask: 0,
+            server_time_interval: 0,
+            last_response_result: Ok(()),
+            client_event: None,
+            client_event_queue: EventQueue::new(MAX_CLIENT_EVENT_COUNT),
+        }
+    }
+
+    /// Stores the recognized bits of a notification mask, dropping any others.
+    pub fn set_notification_mask(&mut self, notification_mask: u32) {
+        let unknown_bits = notification_mask & !VALID_NOTIFICATION_MASK;
+        if unknown_bits != 0 {
+            log::debug(&format!(
+                "Ignoring unknown notification mask bits {:08x}",
+                unknown_bits
+            ));
+        }
+
+        self.notification_mask = notification_mask & VALID_NOTIFICATION_MASK;
+    }
+
+    /// A set mask bit turns off events of that type, so the default mask of 0 allows every event.
+    pub fn is_event_masked(&self, event_type: u8) -> bool {
+        match event_type.checked_sub(1) {
+            Some(bit) if bit < 32 => self.notification_mask & (1 << bit) != 0,
+            _ => false,
+        }
+    }
+}
+
+/// The my data fields a client can change at once with SetMyData.
+pub struct MyDataUpdate<'a> {
+    pub is_public_mode: bool,
+    pub is_show_game_mode: bool,
+    pub is_show_played_game: bool,
+    pub my_favorite_game: GameKey,
+    pub personal_comment: &'a str,
+    /// A partial profile update, see [merge_profile_update].
+    pub profile: FriendProfile,
+}
+
+/// Context needed for the FRD services.
+pub struct FriendServiceContext {
+    pub ndm_wifi_event_handle: Handle,
+    pub ndm_wifi_state: u8,
+    pub wifi_connection_status: WiFiConnectionStatus,
+    /// The mapped wifi state before the current request changed the connection status.
+    pub wifi_state_before_change: Option<u32>,
+    pub counter: u32,
+    /// How many times NASC requests are sent when the server asks for a retry.
+    pub nasc_attempt_count: usize,
+    pub logged_in: bool,
+    pub account_config: AccountConfig,
+    pub my_data: MyData,
+    pub my_online_activity: OnlineActivity,
+    pub nat_properties: NatProperties,
+    /// Whether a NAT detection has finished, since detected properties can also be 0.
+    pub has_detected_nat_properties: bool,
+    pub friend_list: Vec<FriendEntry>,
+    pub online_friends: Vec<FriendKey>,
+    pub session_contexts: Vec<SessionContext>,
+    /// Stands in for the save archive on the host, so save writes can be read back.
+    #[cfg(not(target_os = "horizon"))]
+    pub(super) save_archive: MemoryArchive,
+    // This needs to be an array so we can guarantee the pointer
+    // to the underlying data never changes.
+    // This is important for FrdUCommand::GetFriendKeyList.
+    pub(super) friend_key_list: [FriendKey; MAX_FRIEND_COUNT],
+}
+
+impl FriendServiceContext {
+    /// Session contexts are kept in the same order as the service manager's sessions,
+    /// so closing a session shifts the indexes after it down the same way.
+    pub fn accept_session(&mut self, session_index: usize) {
+        let session_context = SessionContext::new();
+        self.session_contexts.insert(session_index, session_context);
+    }
+
+    pub fn close_session(&mut self, session_index: usize) {
+        if session_index < self.session_contexts.len() {
+            self.session_contexts.remove(session_index);
+        }
+    }
+
+    pub fn set_detected_nat_properties(&mut self, nat_properties: NatProperties) {
+        self.nat_properties = nat_properties;
+        self.has_detected_nat_properties = true;
+    }
+
+    pub fn get_session_client_infos(&self) -> Vec<SessionClientInfo> {
+        self.session_contexts
+            .iter()
+            .map(|session_context| SessionClientInfo {
+                process_id: session_context.process_id,
+                client_sdk_version: session_context.client_sdk_version,
+            })
+            .collect()
+    }
+
+    /// Whether the console has an account linked, which is needed for anything online.
+    pub fn is_account_configured(&self) -> bool {
+        self.account_config.principal_id != 0
+    }
+
+    pub fn get_my_friend_key(&self) -> FriendKey {
+        FriendKey {
+            local_friend_code: self.account_config.local_friend_code,
+            padding: 0,
+            principal_id: self.account_config.principal_id,
+        }
+    }
+
+    /// Accounts without a password have nothing to log in to online play with.
+    pub fn get_my_password(&self) -> Result<&str, FrdErrorCode> {
+        let nex_password = &self.account_config.nex_password;
+
+        if nex_password.is_empty() {
+            return Err(FrdErrorCode::MissingData);
+        }
+
+        Ok(nex_password)
+    }
+
+    /// Whether there's local user data, which an uninitialized console won't have.
+    pub fn has_user_data(&self) -> bool {
+        self.is_account_configured()
+    }
+
+    pub fn ensure_account_configured(&self) -> Result<(), FrdErrorCode> {
+        if self.is_account_configured() {
+            Ok(())
+        } else {
+            Err(FrdErrorCode::InvalidPrincipalId)
+        }
+    }
+
+    pub fn login(&mut self) -> Result<(), FrdErrorCode> {
+        self.ensure_account_configured()?;
+        self.logged_in = true;
+        Ok(())
+    }
+
+    /// Logs out and drops the session's NASC responses, so their tokens can't be handed out afterwards.
+    pub fn logout(&mut self, session_index: usize) {
+        self.logged_in = false;
+
+        let session_context = &mut self.session_contexts[session_index];
+        session_context.last_game_authentication_response = None;
+        session_context.last_service_locator_response = None;
+        session_context.server_time_interval = 0;
+    }
+
+    /// Only kept in memory, so the counter starts over when the sysmodule restarts.
+    pub fn increment_account_config_counter(&mut self) {
+        self.counter = self.counter.wrapping_add(1);
+    }
+}
+
+impl FriendServiceContext {
+    /// Replaces the in-memory friend list with the one in the archive.
+    ///
+    /// Requests are handled one at a time, so no other request can see a partially replaced list.
+    pub fn reload_friend_list_from<A: SaveArchive>(
+        &mut self,
+        archive: &A,
+        path: &str,
+    ) -> CtrResult<()> {
+        let friend_list = read_friend_list(archive, path)?;
+
+        self.friend_key_list = [Default::default(); MAX_FRIEND_COUNT];
+        for (index, friend) in friend_list.iter().enumerate() {
+            self.friend_key_list[index] = friend.friend_key;
+        }
+
+        self.friend_list = friend_list;
+        let friend_list = &self.friend_list;
+        self.online_friends.retain(|friend_key| {
+            friend_list
+                .iter()
+                .any(|friend| friend.friend_key.matches(friend_key))
+        });
+
+        Ok(())
+    }
+
+    /// Checks a friend can be added, so a bad request can be rejected before the server is involved.
+    /// Adding an existing friend is always allowed, since it doesn't change anything.
+    pub fn check_can_add_friend(&self, friend_key: &FriendKey) -> Result<(), FrdErrorCode> {
+        if friend_key.principal_id == 0 {
+            return Err(FrdErrorCode::InvalidArguments);
+        }
+
+        if self.get_friend_by_friend_key(friend_key).is_none()
+            && self.friend_list.len() >= MAX_FRIEND_COUNT
+        {
+            return Err(FrdErrorCode::FriendListFull);
+        }
+
+        Ok(())
+    }
+
+    /// Adds a friend to the friend list in memory, doing nothing if they're already a friend.
+    pub fn add_friend(&mut self, friend_key: FriendKey) -> Result<(), FrdErrorCode> {
+        self.check_can_add_friend(&friend_key)?;
+
+        if self.get_friend_by_friend_key(&friend_key).is_some() {
+            return Ok(());
+        }
+
+        self.friend_list.push(FriendEntry::new(
+            friend_key,
+            Default::default(),
+            Default::default(),
+        ));
+        Ok(())
+    }
+
+    pub fn get_friend_keys(&mut self) -> &[FriendKey] {
+        for (index, friend) in self.friend_list.iter().enumerate() {
+            self.friend_key_list[index] = friend.friend_key;
+        }
+
+        &self.friend_key_list[..self.friend_list.len()]
+    }
+
+    pub fn get_friend_by_friend_key(&self, friend_key: &FriendKey) -> Option<&FriendEntry> {
+        self.friend_list
+            .iter()
+            .find(|friend_entry| friend_entry.friend_key.matches(friend_key))
+    }
+
+    pub fn get_friend_by_principal_id(&self, principal_id: u32) -> Option<&FriendEntry> {
+        if principal_id == 0 {
+            return None;
+        }
+
+        self.friend_list
+            .iter()
+            .find(|friend_entry| friend_entry.friend_key.principal_id == principal_id)
+    }
+
+    /// Callers pass either a friend's local friend code or the code derived from their principal id,
+    /// which can differ, so either one counts as a match.
+    /// Matching only the local friend code under-reports friends for the derived form.
+    pub fn is_included_in_friend_list(&self, friend_code: u64) -> bool {
+        let has_local_friend_code = self
+            .friend_list
+            .iter()
+            .any(|friend| friend.friend_key.local_friend_code == friend_code);
+
+        has_local_friend_code
+            || convert_friend_code_to_principal_id(friend_code)
+                .ok()
+                .and_then(|principal_id| self.get_friend_by_principal_id(principal_id))
+                .is_some()
+    }
+
+    /// Sets the personal comment, truncated the same way GetMyComment truncates it.
+    pub fn set_personal_comment(&mut self, personal_comment: &str) {
+        self.my_data.personal_comment = truncate_comment(personal_comment);
+    }
+
+    /// Applies every field of the update, or none of them if the merged profile isn't valid.
+    pub fn set_my_data(&mut self, update: MyDataUpdate) -> Result<(), FrdErrorCode> {
+        let profile = merge_profile_update(&self.my_data.profile, &update.profile);
+        validate_profile(&profile).map_err(|_| FrdErrorCode::InvalidArguments)?;
+
+        self.my_data.profile = profile;
+        self.my_data.is_public_mode = update.is_public_mode;
+        self.my_data.is_show_game_mode = update.is_show_game_mode;
+        self.my_data.is_show_played_game = update.is_show_played_game;
+        self.my_data.my_favorite_game = update.my_favorite_game;
+        self.set_personal_comment(update.personal_comment);
+        Ok(())
+    }
+
+    /// Writes my data to the archive, keeping the fields that aren't loaded from the existing file.
+    pub fn save_my_data_to<A: SaveArchive>(&self, archive: &mut A, path: &str) -> CtrResult<()> {
+        let original_raw_data = archive.read(path, 0, MY_DATA_FILE_SIZE)?;
+        let raw_data = self.my_data.to_le_bytes(&original_raw_data)?;
+        atomic_write(archive, path, &raw_data)
+    }
+
+    /// Sets the presence reported by GetMyPresence from a raw client buffer.
+    pub fn set_my_presence(&mut self, raw_presence: &[u8]) -> Result<(), FrdErrorCode> {
+        if raw_presence.len() != mem::size_of::<ExpandedFriendPresence>() {
+            return Err(FrdErrorCode::InvalidArguments);
+        }
+
+        self.my_online_activity.presence = raw_presence
+            .read_le(0)
+            .map_err(|_| FrdErrorCode::InvalidArguments)?;
+        Ok(())
+    }
+
+    /// Replaces the description part of the presence, leaving the game mode as it was.
+    /// The description is raw UTF-16, and the rest of the field is zeroed.
+    pub fn set_game_mode_description(&mut self, description: &[u8]) -> Result<(), FrdErrorCode> {
+        if description.len() > GAME_MODE_DESCRIPTION_SIZE || description.len() % 2 != 0 {
+            return Err(FrdErrorCode::InvalidArguments);
+        }
+
+        let mut raw_presence = vec![0; mem::size_of::<ExpandedFriendPresence>()];
+        raw_presence.checked_write_le(0, &self.my_online_activity.presence);
+
+        let description_field = raw_presence
+            .get_mut(
+                GAME_MODE_DESCRIPTION_OFFSET
+                    ..GAME_MODE_DESCRIPTION_OFFSET + GAME_MODE_DESCRIPTION_SIZE,
+            )
+            .ok_or(FrdErrorCode::InvalidArguments)?;
+        description_field.fill(0);
+        description_field[..description.len()].copy_from_slice(description);
+
+        self.set_my_presence(&raw_presence)
+    }
+
+    /// There's no server to deliver invitations, so they're queued for the other local sessions
+    /// listening for events. Returns the indexes of the sessions that need to be signaled.
+    pub fn send_invitation(
+        &mut self,
+        session_index: usize,
+        friend_keys: &[FriendKey],
+    ) -> Result<Vec<usize>, FrdErrorCode> {
+        let has_unknown_friend = friend_keys
+            .iter()
+            .any(|friend_key| self.get_friend_by_friend_key(friend_key).is_none());
+
+        if has_unknown_friend {
+            return Err(FrdErrorCode::InvalidArguments);
+        }
+
+        let event = create_friend_sent_invitation_event(&self.get_my_friend_key());
+        Ok(self.queue_event_for_other_sessions(session_index, event))
+    }
+
+    /// Other listening sessions are told the user's presence changed.
+    /// Returns the indexes of the sessions that need to be signaled.
+    pub fn notify_my_presence_changed(&mut self, session_index: usize) -> Vec<usize> {
+        let event = create_self_presence_changed_event(&self.get_my_friend_key());
+        self.queue_event_for_other_sessions(session_index, event)
+    }
+
+    /// Queues an event for every listening session except the one that caused it,
+    /// skipping sessions that masked out the event's type.
+    /// Returns the indexes of the sessions that need to be signaled.
+    fn queue_event_for_other_sessions(
+        &mut self,
+        session_index: usize,
+        event: NotificationEvent,
+    ) -> Vec<usize> {
+        let event_type = get_notification_event_type(&event);
+        let mut signaled_session_indexes = vec![];
+
+        for (index, session_context) in self.session_contexts.iter_mut().enumerate() {
+            if index != session_index
+                && session_context.client_event.is_some()
+                && !session_context.is_event_masked(event_type)
+            {
+                session_context.client_event_queue.push(event);
+                signaled_session_indexes.push(index);
+            }
+        }
+
+        signaled_session_indexes
+    }
+
+    pub fn signal_client_events(&self, session_indexes: &[usize]) -> CtrResult<()> {
+        for index in session_indexes {
+            if let Some(client_event) = &self.session_contexts[*index].client_event {
+                svc::signal_event(client_event)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn set_friend_online(
+        &mut self,
+        friend_key: &FriendKey,
+        is_online: bool,
+    ) -> Result<(), FrdErrorCode> {
+        if self.get_friend_by_friend_key(friend_key).is_none() {
+            return Err(FrdErrorCode::InvalidArguments);
+        }
+
+        self.online_friends
+            .retain(|online_friend_key| !online_friend_key.matches(friend_key));
+
+        if is_online {
+            self.online_friends.push(*friend_key);
+        }
+
+        Ok(())
+    }
+
+    /// Retail only unscrambles the codes of friends, and returns 0 for anything else.
+    /// Including non-friends also returns any structurally valid code,
+    /// and IsIncludedInFriendList still tells whether it belongs to a friend.
+    pub fn get_unscrambled_local_friend_code(
+        &self,
+        friend_code: u64,
+        include_non_friends: bool,
+    ) -> u64 {
+        let is_in_friend_list = self
+            .friend_list
+            .iter()
+            .any(|friend| friend.friend_key.local_friend_code == friend_code);
+
+        if is_in_friend_list || (include_non_friends && validate_friend_code(friend_code)) {
+            friend_code
+        } else {
+            0
+        }
+    }
+
+    pub fn is_friend_online(&self, friend_key: &FriendKey) -> bool {
+        self.online_friends
+            .iter()
+            .any(|online_friend_key| online_friend_key.matches(friend_key))
+    }
+
+    /// Unknown and offline friends have the default presence.
+    pub fn get_friend_presence(&self, friend_key: &FriendKey) -> FriendPresence {
+        FriendPresence {
+            is_online: self.is_friend_online(friend_key),
+            ..Default::default()
+        }
+    }
+
+    /// Gets the friend info for a page of the requested friend keys,
+    /// skipping `offset` keys like GetFriendKeyList does.
+    /// Unknown friends have the default friend info.
+    pub fn get_friend_info_page(
+        &self,
+        friend_keys: &[FriendKey],
+        offset: usize,
+        max_count: usize,
+        character_set: u32,
+    ) -> Vec<FriendInfo> {
+        let start = min(offset, friend_keys.len());
+        let end = min(start.saturating_add(max_count), friend_keys.len());
+
+        friend_keys[start..end]
+            .iter()
+            .map(|friend_key| {
+                self.get_friend_by_friend_key(friend_key)
+                    .map(|friend| friend.get_friend_info(character_set))
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+
+    /// Unknown friends have a zeroed timestamp.
+    pub fn get_friend_last_online(&self, friend_key: &FriendKey) -> SystemTimestamp {
+        match self.get_friend_by_friend_key(friend_key) {
+            Some(friend) => friend.last_online.into(),
+            None => SystemTimestamp::new(0),
+        }
+    }
+
+    pub fn copy_into_session_static_buffer<T: EndianWrite + Sized>(
+        &mut self,
+        session_index: usize,
+        data: &[T],
+    ) -> &[u8] {
+        let static_buffer = &mut self.session_contexts[session_index].static_buffer;
+        static_buffer.clear();
+        static_buffer.resize(data.len() * mem::size_of::<T>(), 0);
+        let mut stream = StreamContainer::new(static_buffer.as_mut_slice());
+
+        for datum in data.iter() {
+            stream.checked_write_stream_le(datum);
+        }
+
+        stream.into_raw()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frd::save::friend_list::{
+        FriendListHeader, FRIEND_LIST_HEADER_SIZE, FRIEND_LIST_MAGIC,
+    };
+    use mock::{create_mock_friend_last_online, MOCK_FRIEND_KEY};
+
+    mod get_my_password {
+        use super::*;
+
+        #[test]
+        fn should_return_the_password() {
+            let context = FriendServiceContext::new().unwrap();
+            assert_eq!(context.get_my_password(), Ok("password"));
+        }
+
+        #[test]
+        fn should_return_missing_data_when_there_is_no_password() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.account_config.nex_password.clear();
+            assert_eq!(context.get_my_password(), Err(FrdErrorCode::MissingData));
+        }
+    }
+
+    mod set_notification_mask {
+        use super::*;
+
+        #[test]
+        fn should_keep_known_bits() {
+            let mut session_context = SessionContext::new();
+            session_context.set_notification_mask(0x101);
+            assert_eq!(session_context.notification_mask, 0x101);
+        }
+
+        #[test]
+        fn should_strip_unknown_bits() {
+            let mut session_context = SessionContext::new();
+            session_context.set_notification_mask(0xffff0003);
+            assert_eq!(session_context.notification_mask, 0x3);
+        }
+    }
+
+    mod is_event_masked {
+        use super::*;
+
+        #[test]
+        fn should_allow_every_event_by_default() {
+            let session_context = SessionContext::new();
+            assert!((1..=9).all(|event_type| !session_context.is_event_masked(event_type)));
+        }
+
+        #[test]
+        fn should_mask_an_event_type_by_its_bit() {
+            let mut session_context = SessionContext::new();
+            session_context.set_notification_mask(0x100);
+            assert!(session_context.is_event_masked(FRIEND_SENT_INVITATION_EVENT));
+            assert!(!session_context.is_event_masked(FRIEND_UPDATED_PRESENCE_EVENT));
+        }
+
+        #[test]
+        fn should_not_mask_an_event_without_a_type() {
+            let mut session_context = SessionContext::new();
+            session_context.set_notification_mask(VALID_NOTIFICATION_MASK);
+            assert!(!session_context.is_event_masked(0));
+        }
+    }
+
+    mod ensure_account_configured {
+        use super::*;
+
+        #[test]
+        fn should_return_ok_when_an_account_is_configured() {
+            let context = FriendServiceContext::new().unwrap();
+            assert!(context.is_account_configured());
+            assert_eq!(context.ensure_account_configured(), Ok(()));
+        }
+
+        #[test]
+        fn should_return_an_error_when_the_principal_id_is_0() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.account_config.principal_id = 0;
+
+            assert!(!context.is_account_configured());
+            assert_eq!(
+                context.ensure_account_configured(),
+                Err(FrdErrorCode::InvalidPrincipalId)
+            );
+        }
+    }
+
+    mod copy_into_session_static_buffer {
+        use super::*;
+
+        #[test]
+        fn should_reserve_the_buffer_when_a_session_is_accepted() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.accept_session(0);
+
+            let capacity = context.session_contexts[0].static_buffer.capacity();
+            assert!(capacity >= STATIC_BUFFER_RESERVE_SIZE);
+        }
+
+        #[test]
+        fn should_keep_the_capacity_after_a_smaller_call() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.accept_session(0);
+            let friend_keys = [FriendKey::default(); MAX_FRIEND_COUNT * 2];
+
+            context.copy_into_session_static_buffer(0, &friend_keys);
+            let large_capacity = context.session_contexts[0].static_buffer.capacity();
+            let result = context.copy_into_session_static_buffer(0, &friend_keys[..1]);
+
+            assert_eq!(result.len(), mem::size_of::<FriendKey>());
+            assert_eq!(
+                context.session_contexts[0].static_buffer.capacity(),
+                large_capacity
+            );
+        }
+    }
+
+    mod close_session {
+        use super::*;
+
+        #[test]
+        fn should_shift_later_sessions_down_with_their_state() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.accept_session(0);
+            context.accept_session(1);
+            context.accept_session(2);
+            context.session_contexts[0].process_id = 0x20;
+            context.session_contexts[1].process_id = 0x21;
+            context.session_contexts[2].process_id = 0x22;
+            context.session_contexts[2].client_event = Some(0.into());
+
+            context.close_session(1);
+
+            assert_eq!(context.session_contexts.len(), 2);
+            assert_eq!(context.session_contexts[0].process_id, 0x20);
+            assert_eq!(context.session_contexts[1].process_id, 0x22);
+            assert!(context.session_contexts[1].client_event.is_some());
+        }
+
+        #[test]
+        fn should_ignore_a_session_that_isnt_open() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.accept_session(0);
+
+            context.close_session(3);
+
+            assert_eq!(context.session_contexts.len(), 1);
+        }
+    }
+
+    mod set_detected_nat_properties {
+        use super::*;
+
+        #[test]
+        fn should_not_be_detected_before_a_detection() {
+            let context = FriendServiceContext::new().unwrap();
+
+            assert!(!context.has_detected_nat_properties);
+            assert_eq!(context.nat_properties.get_unk1(), 0);
+            assert_eq!(context.nat_properties.get_unk2(), 0);
+        }
+
+        #[test]
+        fn should_mark_zero_properties_as_detected() {
+            let mut context = FriendServiceContext::new().unwrap();
+
+            context.set_detected_nat_properties(Default::default());
+
+            assert!(context.has_detected_nat_properties);
+            assert_eq!(context.nat_properties.get_unk1(), 0);
+            assert_eq!(context.nat_properties.get_unk2(), 0);
+        }
+    }
+
+    mod get_session_client_infos {
+        use super::*;
+
+        #[test]
+        fn should_report_every_session() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.accept_session(0);
+            context.accept_session(1);
+            context.session_contexts[0].process_id = 0x20;
+            context.session_contexts[0].client_sdk_version = 0x70000c8;
+            context.session_contexts[1].process_id = 0x21;
+            context.session_contexts[1].client_sdk_version = 0xb0502c8;
+
+            let result = context.get_session_client_infos();
+
+            assert_eq!(
+                result,
+                [
+                    SessionClientInfo {
+                        process_id: 0x20,
+                        client_sdk_version: 0x70000c8,
+                    },
+                    SessionClientInfo {
+                        process_id: 0x21,
+                        client_sdk_version: 0xb0502c8,
+                    },
+                ]
+            );
+        }
+    }
+
+    mod has_user_data {
+        use super::*;
+
+        #[test]
+        fn should_return_true_when_an_account_is_configured() {
+            let context = FriendServiceContext::new().unwrap();
+            assert!(context.has_user_data());
+        }
+
+        #[test]
+        fn should_return_false_for_an_unconfigured_console() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.account_config.principal_id = 0;
+            assert!(!context.has_user_data());
+        }
+    }
+
+    mod login {
+        use super::*;
+
+        #[test]
+        fn should_log_in_when_an_account_is_configured() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.login().expect("Should have logged in");
+            assert!(context.logged_in);
+        }
+
+        #[test]
+        fn should_return_an_error_without_an_account() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.account_config.principal_id = 0;
+
+            let error_code = context.login().expect_err("Expected error code");
+            assert_eq!(error_code, FrdErrorCode::InvalidPrincipalId);
+            assert!(!context.logged_in);
+        }
+    }
+
+    mod logout {
+        use super::*;
+
+        #[test]
+        fn should_clear_the_logged_in_flag() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.accept_session(0);
+            context.login().unwrap();
+            context.logout(0);
+            assert!(!context.logged_in);
+        }
+
+        #[test]
+        fn should_clear_the_session_nasc_data() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.accept_session(0);
+            let session_context = &mut context.session_contexts[0];
+            session_context.last_game_authentication_response =
+                Some(CachedResponse::new(Default::default()));
+            session_context.last_service_locator_response =
+                Some(CachedResponse::new(Default::default()));
+            session_context.server_time_interval = 10;
+
+            context.logout(0);
+
+            let session_context = &context.session_contexts[0];
+            assert!(session_context.last_game_authentication_response.is_none());
+            assert!(session_context.last_service_locator_response.is_none());
+            assert_eq!(session_context.server_time_interval, 0);
+        }
+    }
+
+    mod increment_account_config_counter {
+        use super::*;
+
+        #[test]
+        fn should_increment_the_counter() {
+            let mut context = FriendServiceContext::new().unwrap();
+
+            context.increment_account_config_counter();
+            context.increment_account_config_counter();
+
+            assert_eq!(context.counter, 2);
+        }
+
+        #[test]
+        fn should_wrap_around() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.counter = u32::MAX;
+
+            context.increment_account_config_counter();
+
+            assert_eq!(context.counter, 0);
+        }
+    }
+
+    mod get_friend_last_online {
+        use super::*;
+
+        #[test]
+        fn should_return_the_last_online_timestamp_of_a_friend() {
+            let context = FriendServiceContext::new().unwrap();
+            let result = context.get_friend_last_online(&MOCK_FRIEND_KEY);
+            let expected: SystemTimestamp = create_mock_friend_last_online().into();
+            assert_eq!(result.get_unix_timestamp(), expected.get_unix_timestamp());
+        }
+
+        #[test]
+        fn should_return_a_zero_timestamp_for_an_unknown_friend() {
+            let context = FriendServiceContext::new().unwrap();
+            let result = context.get_friend_last_online(&FriendKey::default());
+            assert_eq!(result.get_unix_timestamp(), 0);
+        }
+    }
+
+    mod get_friend_by_principal_id {
+        use super::*;
+
+        #[test]
+        fn should_return_the_friend_with_the_principal_id() {
+            let context = FriendServiceContext::new().unwrap();
+            let result = context
+                .get_friend_by_principal_id(MOCK_FRIEND_KEY.principal_id)
+                .map(|friend| friend.friend_key);
+            assert_eq!(result, Some(MOCK_FRIEND_KEY));
+        }
+
+        #[test]
+        fn should_return_none_for_an_unknown_principal_id() {
+            let context = FriendServiceContext::new().unwrap();
+            assert!(context.get_friend_by_principal_id(0x55667788).is_none());
+            assert!(context.get_friend_by_principal_id(0).is_none());
+        }
+    }
+
+    mod is_included_in_friend_list {
+        use super::*;
+        use crate::frd::utils::convert_principal_id_to_friend_code;
+
+        #[test]
+        fn should_match_a_local_friend_code() {
+            let context = FriendServiceContext::new().unwrap();
+            assert!(context.is_included_in_friend_list(MOCK_FRIEND_KEY.local_friend_code));
+        }
+
+        #[test]
+        fn should_match_a_friend_code_derived_from_a_principal_id() {
+            let context = FriendServiceContext::new().unwrap();
+            let friend_code =
+                convert_principal_id_to_friend_code(MOCK_FRIEND_KEY.principal_id).unwrap();
+            assert_ne!(friend_code, MOCK_FRIEND_KEY.local_friend_code);
+            assert!(context.is_included_in_friend_list(friend_code));
+        }
+
+        #[test]
+        fn should_not_match_a_non_friend() {
+            let context = FriendServiceContext::new().unwrap();
+            let friend_code = convert_principal_id_to_friend_code(0x55667788).unwrap();
+            assert!(!context.is_included_in_friend_list(friend_code));
+            assert!(!context.is_included_in_friend_list(0));
+        }
+    }
+
+    mod set_personal_comment {
+        use super::*;
+        use crate::frd::utils::encode_comment;
+
+        #[test]
+        fn should_get_exactly_the_first_16_code_units() {
+            let mut context = FriendServiceContext::new().unwrap();
+
+            context.set_personal_comment("abcdefghijklmnopqrstuvwxyz");
+
+            let expected: Vec<u16> = "abcdefghijklmnop".encode_utf16().chain([0]).collect();
+            assert_eq!(context.my_data.personal_comment, "abcdefghijklmnop");
+            assert_eq!(
+                encode_comment(&context.my_data.personal_comment)[..],
+                expected[..]
+            );
+        }
+    }
+
+    mod set_my_presence {
+        use super::*;
+
+        #[test]
+        fn should_round_trip_a_presence() {
+            let presence_size = mem::size_of::<ExpandedFriendPresence>();
+            let raw_presence: Vec<u8> = (0..presence_size).map(|index| index as u8).collect();
+            let mut context = FriendServiceContext::new().unwrap();
+            context.accept_session(0);
+
+            context
+                .set_my_presence(&raw_presence)
+                .expect("Should have set the presence");
+
+            let presence = context.my_online_activity.presence;
+            let result = context.copy_into_session_static_buffer(0, &[presence]);
+            assert_eq!(result, raw_presence.as_slice());
+        }
+
+        #[test]
+        fn should_return_an_error_for_the_wrong_size() {
+            let presence_size = mem::size_of::<ExpandedFriendPresence>();
+            let mut context = FriendServiceContext::new().unwrap();
+
+            let result = context.set_my_presence(&vec![0; presence_size - 1]);
+
+            assert_eq!(result, Err(FrdErrorCode::InvalidArguments));
+            assert_eq!(
+                context.my_online_activity.presence,
+                ExpandedFriendPresence::default()
+            );
+        }
+    }
+
+    mod set_game_mode_description {
+        use super::*;
+
+        #[test]
+        fn should_replace_only_the_description() {
+            let presence_size = mem::size_of::<ExpandedFriendPresence>();
+            let mut context = FriendServiceContext::new().unwrap();
+            context.set_my_presence(&vec![0xff; presence_size]).unwrap();
+
+            context
+                .set_game_mode_description(&[0x41, 0x00, 0x42, 0x00])
+                .expect("Should have set the description");
+
+            let mut raw_presence = vec![0; presence_size];
+            raw_presence.checked_write_le(0, &context.my_online_activity.presence);
+            let description = &raw_presence[GAME_MODE_DESCRIPTION_OFFSET..];
+            assert_eq!(&raw_presence[..GAME_MODE_DESCRIPTION_OFFSET], &[0xff; 0x2c]);
+            assert_eq!(&description[..4], &[0x41, 0x00, 0x42, 0x00]);
+            assert!(description[4..GAME_MODE_DESCRIPTION_SIZE]
+                .iter()
+                .all(|byte| *byte == 0));
+        }
+
+        #[test]
+        fn should_return_an_error_for_an_overlong_description() {
+            let mut context = FriendServiceContext::new().unwrap();
+
+            let result = context.set_game_mode_description(&[0x41; GAME_MODE_DESCRIPTION_SIZE + 2]);
+
+            assert_eq!(result, Err(FrdErrorCode::InvalidArguments));
+        }
+
+        #[test]
+        fn should_return_an_error_for_a_partial_character() {
+            let mut context = FriendServiceContext::new().unwrap();
+
+            let result = context.set_game_mode_description(&[0x41, 0x00, 0x42]);
+
+            assert_eq!(result, Err(FrdErrorCode::InvalidArguments));
+        }
+    }
+
+    mod send_invitation {
+        use super::*;
+
+        fn create_listening_context() -> FriendServiceContext {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.accept_session(0);
+            context.accept_session(1);
+            context.session_contexts[1].client_event = Some(0.into());
+            context
+        }
+
+        #[test]
+        fn should_queue_an_event_for_listening_sessions() {
+            let mut context = create_listening_context();
+
+            let result = context.send_invitation(0, &[MOCK_FRIEND_KEY]);
+
+            assert_eq!(result, Ok(vec![1]));
+            assert!(context.session_contexts[0].client_event_queue.is_empty());
+
+            let my_friend_key = context.get_my_friend_key();
+            let events: Vec<NotificationEvent> = context.session_contexts[1]
+                .client_event_queue
+                .drain(1)
+                .collect();
+            assert_eq!(
+                events,
+                [create_friend_sent_invitation_event(&my_friend_key)]
+            );
+        }
+
+        #[test]
+        fn should_return_an_error_for_an_unknown_friend() {
+            let mut context = create_listening_context();
+
+            let result = context.send_invitation(0, &[MOCK_FRIEND_KEY, FriendKey::default()]);
+
+            assert_eq!(result, Err(FrdErrorCode::InvalidArguments));
+            assert!(context.session_contexts[1].client_event_queue.is_empty());
+        }
+    }
+
+    mod notify_my_presence_changed {
+        use super::*;
+
+        fn create_listening_context() -> FriendServiceContext {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.accept_session(0);
+            context.accept_session(1);
+            context.session_contexts[1].client_event = Some(0.into());
+            context
+        }
+
+        #[test]
+        fn should_queue_an_event_for_other_listening_sessions() {
+            let mut context = create_listening_context();
+
+            let result = context.notify_my_presence_changed(0);
+
+            assert_eq!(result, vec![1]);
+            assert!(context.session_contexts[0].client_event_queue.is_empty());
+
+            let my_friend_key = context.get_my_friend_key();
+            let events: Vec<NotificationEvent> = context.session_contexts[1]
+                .client_event_queue
+                .drain(1)
+                .collect();
+            assert_eq!(events, [create_self_presence_changed_event(&my_friend_key)]);
+        }
+
+        #[test]
+        fn should_skip_sessions_that_masked_out_presence_events() {
+            let mut context = create_listening_context();
+            context.session_contexts[1].set_notification_mask(0x8);
+
+            let result = context.notify_my_presence_changed(0);
+
+            assert_eq!(result, Vec::<usize>::new());
+            assert!(context.session_contexts[1].client_event_queue.is_empty());
+        }
+    }
+
+    mod add_friend {
+        use super::*;
+
+        fn create_friend_key(principal_id: u32) -> FriendKey {
+            FriendKey {
+                principal_id,
+                padding: 0,
+                local_friend_code: 0,
+            }
+        }
+
+        #[test]
+        fn should_add_a_new_friend() {
+            let mut context = FriendServiceContext::new().unwrap();
+
+            context
+                .add_friend(create_friend_key(0x55667788))
+                .expect("Should have added the friend");
+
+            assert_eq!(
+                context.get_friend_keys(),
+                &[MOCK_FRIEND_KEY, create_friend_key(0x55667788)]
+            );
+        }
+
+        #[test]
+        fn should_not_duplicate_an_existing_friend() {
+            let mut context = FriendServiceContext::new().unwrap();
+
+            context
+                .add_friend(MOCK_FRIEND_KEY)
+                .expect("Should have been a no-op");
+
+            assert_eq!(context.get_friend_keys(), &[MOCK_FRIEND_KEY]);
+        }
+
+        #[test]
+        fn should_return_an_error_when_the_friend_list_is_full() {
+            let mut context = FriendServiceContext::new().unwrap();
+            for principal_id in 1..MAX_FRIEND_COUNT as u32 {
+                context.add_friend(create_friend_key(principal_id)).unwrap();
+            }
+
+            let result = context.add_friend(create_friend_key(0x55667788));
+
+            assert_eq!(result, Err(FrdErrorCode::FriendListFull));
+            assert_eq!(context.friend_list.len(), MAX_FRIEND_COUNT);
+        }
+
+        #[test]
+        fn should_return_an_error_for_an_invalid_friend_key() {
+            let mut context = FriendServiceContext::new().unwrap();
+
+            let result = context.add_friend(create_friend_key(0));
+
+            assert_eq!(result, Err(FrdErrorCode::InvalidArguments));
+            assert_eq!(context.get_friend_keys(), &[MOCK_FRIEND_KEY]);
+        }
+
+        #[test]
+        fn should_allow_an_existing_friend_when_the_friend_list_is_full() {
+            let mut context = FriendServiceContext::new().unwrap();
+            for principal_id in 1..MAX_FRIEND_COUNT as u32 {
+                context.add_friend(create_friend_key(principal_id)).unwrap();
+            }
+
+            let result = context.add_friend(MOCK_FRIEND_KEY);
+
+            assert_eq!(result, Ok(()));
+        }
+    }
+
+    mod set_friend_online {
+        use super::*;
+
+        #[test]
+        fn should_change_the_presence_of_a_friend() {
+            let mut context = FriendServiceContext::new().unwrap();
+            let offline_presence = context.get_friend_presence(&MOCK_FRIEND_KEY);
+            assert_eq!(offline_presence, FriendPresence::default());
+
+            context
+                .set_friend_online(&MOCK_FRIEND_KEY, true)
+                .expect("Should have set the friend online");
+            assert!(context.is_friend_online(&MOCK_FRIEND_KEY));
+            assert_ne!(
+                context.get_friend_presence(&MOCK_FRIEND_KEY),
+                offline_presence
+            );
+
+            context
+                .set_friend_online(&MOCK_FRIEND_KEY, false)
+                .expect("Should have set the friend offline");
+            assert_eq!(
+                context.get_friend_presence(&MOCK_FRIEND_KEY),
+                offline_presence
+            );
+        }
+
+        #[test]
+        fn should_return_an_error_for_an_unknown_friend() {
+            let mut context = FriendServiceContext::new().unwrap();
+            let result = context.set_friend_online(&FriendKey::default(), true);
+            assert_eq!(result, Err(FrdErrorCode::InvalidArguments));
+            assert!(!context.is_friend_online(&FriendKey::default()));
+        }
+
+        #[test]
+        fn should_find_a_friend_whose_key_has_different_padding() {
+            let mut context = FriendServiceContext::new().unwrap();
+            let friend_key = FriendKey {
+                padding: 0xdeadbeef,
+                ..MOCK_FRIEND_KEY
+            };
+
+            context
+                .set_friend_online(&friend_key, true)
+                .expect("Should have set the friend online");
+
+            assert!(context.get_friend_by_friend_key(&friend_key).is_some());
+            assert!(context.is_friend_online(&MOCK_FRIEND_KEY));
+        }
+    }
+
+    mod get_unscrambled_local_friend_code {
+        use super::*;
+        use crate::frd::utils::convert_principal_id_to_friend_code;
+
+        #[test]
+        fn should_return_the_code_of_a_friend() {
+            let context = FriendServiceContext::new().unwrap();
+            let friend_code = MOCK_FRIEND_KEY.local_friend_code;
+
+            assert_eq!(
+                context.get_unscrambled_local_friend_code(friend_code, false),
+                friend_code
+            );
+            assert_eq!(
+                context.get_unscrambled_local_friend_code(friend_code, true),
+                friend_code
+            );
+        }
+
+        #[test]
+        fn should_only_return_a_valid_non_friend_code_when_non_friends_are_included() {
+            let context = FriendServiceContext::new().unwrap();
+            let friend_code = convert_principal_id_to_friend_code(0x55667788).unwrap();
+
+            assert_eq!(
+                context.get_unscrambled_local_friend_code(friend_code, false),
+                0
+            );
+            assert_eq!(
+                context.get_unscrambled_local_friend_code(friend_code, true),
+                friend_code
+            );
+        }
+
+        #[test]
+        fn should_return_0_for_an_invalid_non_friend_code() {
+            let context = FriendServiceContext::new().unwrap();
+            let friend_code = convert_principal_id_to_friend_code(0x55667788).unwrap() ^ 1;
+
+            assert_eq!(
+                context.get_unscrambled_local_friend_code(friend_code, true),
+                0
+            );
+        }
+    }
+
+    mod get_friend_info_page {
+        use super::*;
+
+        fn create_friend_key(principal_id: u32) -> FriendKey {
+            FriendKey {
+                principal_id,
+                padding: 0,
+                local_friend_code: 0,
+            }
+        }
+
+        #[test]
+        fn should_page_through_the_friend_list() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.add_friend(create_friend_key(1)).unwrap();
+            context.add_friend(create_friend_key(2)).unwrap();
+            let friend_keys = context.get_friend_keys().to_vec();
+
+            let first_page = context.get_friend_info_page(&friend_keys, 0, 2, 0);
+            let second_page = context.get_friend_info_page(&friend_keys, 2, 2, 0);
+
+            assert_eq!(first_page.len(), 2);
+            assert_eq!(first_page[0].friend_key, MOCK_FRIEND_KEY);
+            assert_eq!(first_page[1].friend_key, create_friend_key(1));
+            assert_eq!(second_page.len(), 1);
+            assert_eq!(second_page[0].friend_key, create_friend_key(2));
+        }
+
+        #[test]
+        fn should_return_nothing_past_the_end() {
+            let context = FriendServiceContext::new().unwrap();
+            let result = context.get_friend_info_page(&[MOCK_FRIEND_KEY], 5, 2, 0);
+            assert!(result.is_empty());
+        }
+    }
+
+    mod reload_friend_list_from {
+        use super::*;
+
+        #[test]
+        fn should_pick_up_a_changed_friend_list() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.set_friend_online(&MOCK_FRIEND_KEY, true).unwrap();
+
+            let new_friend = FriendEntry {
+                friend_key: FriendKey {
+                    principal_id: 0x55667788,
+                    padding: 0,
+                    local_friend_code: 0x0055667788,
+                },
+                ..Default::default()
+            };
+            let header = FriendListHeader {
+                magic: FRIEND_LIST_MAGIC,
+                ..Default::default()
+            };
+            let mut bytes = vec![];
+            bytes.checked_write_le(0, &header);
+            bytes.checked_write_le(FRIEND_LIST_HEADER_SIZE, &new_friend);
+
+            let mut archive = MemoryArchive::default();
+            archive.files.insert("/1/friendlist".into(), bytes);
+
+            context
+                .reload_friend_list_from(&archive, "/1/friendlist")
+                .expect("Should have reloaded");
+
+            assert_eq!(context.friend_list, vec![new_friend]);
+            assert_eq!(context.friend_key_list[0], new_friend.friend_key);
+            assert_eq!(context.get_friend_keys(), &[new_friend.friend_key]);
+            assert!(!context.is_friend_online(&MOCK_FRIEND_KEY));
+        }
+
+        #[test]
+        fn should_keep_the_old_list_if_the_file_cant_be_read() {
+            let mut context = FriendServiceContext::new().unwrap();
+            let archive = MemoryArchive::default();
+
+            let result = context.reload_friend_list_from(&archive, "/1/friendlist");
+
+            assert!(result.is_err());
+            assert_eq!(context.get_friend_keys(), &[MOCK_FRIEND_KEY]);
+        }
+    }
+
+    mod set_my_data {
+        use super::*;
+        use crate::frd::save::my_data::PROFILE_FIELD_UNCHANGED;
+
+        fn create_profile_update(country: u8, area: u8) -> FriendProfile {
+            FriendProfile {
+                region: PROFILE_FIELD_UNCHANGED,
+                country,
+                area,
+                language: PROFILE_FIELD_UNCHANGED,
+                platform: PROFILE_FIELD_UNCHANGED,
+                padding: [0; 3],
+            }
+        }
+
+        fn create_my_data_update(profile: FriendProfile) -> MyDataUpdate<'static> {
+            MyDataUpdate {
+                is_public_mode: false,
+                is_show_game_mode: false,
+                is_show_played_game: false,
+                my_favorite_game: Default::default(),
+                personal_comment: "Updated",
+                profile,
+            }
+        }
+
+        #[test]
+        fn should_update_each_field() {
+            let mut context = FriendServiceContext::new().unwrap();
+            let my_favorite_game = GameKey {
+                title_id: 0x0004000000123400,
+                version: 1,
+                unk: 0,
+            };
+
+            context
+                .set_my_data(MyDataUpdate {
+                    is_public_mode: false,
+                    is_show_game_mode: false,
+                    is_show_played_game: false,
+                    my_favorite_game,
+                    personal_comment: "Updated",
+                    profile: create_profile_update(PROFILE_FIELD_UNCHANGED, 5),
+                })
+                .expect("Should have set my data");
+
+            assert!(!context.my_data.is_public_mode);
+            assert!(!context.my_data.is_show_game_mode);
+            assert!(!context.my_data.is_show_played_game);
+            assert_eq!(context.my_data.my_favorite_game, my_favorite_game);
+            assert_eq!(context.my_data.personal_comment, "Updated");
+        }
+
+        #[test]
+        fn should_keep_unchanged_profile_fields() {
+            let mut context = FriendServiceContext::new().unwrap();
+
+            context
+                .set_my_data(create_my_data_update(create_profile_update(
+                    PROFILE_FIELD_UNCHANGED,
+                    5,
+                )))
+                .expect("Should have set my data");
+
+            let profile = context.my_data.profile;
+            assert_eq!(profile.region, 1);
+            assert_eq!(profile.country, 49);
+            assert_eq!(profile.area, 5);
+            assert_eq!(profile.language, 1);
+            assert_eq!(profile.platform, 2);
+        }
+
+        #[test]
+        fn should_reject_an_invalid_profile_without_changing_anything() {
+            let mut context = FriendServiceContext::new().unwrap();
+
+            let result = context.set_my_data(create_my_data_update(create_profile_update(
+                0xfe,
+                PROFILE_FIELD_UNCHANGED,
+            )));
+
+            assert_eq!(result, Err(FrdErrorCode::InvalidArguments));
+            assert_eq!(context.my_data.profile.country, 49);
+            assert!(context.my_data.is_public_mode);
+            assert_ne!(context.my_data.personal_comment, "Updated");
+        }
+    }
+
+    mod save_my_data_to {
+        use super::*;
+        use crate::frd::save::my_data::PROFILE_FIELD_UNCHANGED;
+
+        #[test]
+        fn should_persist_every_updated_field() {
+            let mut context = FriendServiceContext::new().unwrap();
+            let mut archive = MemoryArchive::default();
+            archive.files.insert(
+                "/1/mydata".into(),
+                context.my_data.to_le_bytes(&[]).unwrap(),
+            );
+            context
+                .set_my_data(MyDataUpdate {
+                    is_public_mode: false,
+                    is_show_game_mode: true,
+                    is_show_played_game: false,
+                    my_favorite_game: Default::default(),
+                    personal_comment: "Updated",
+                    profile: FriendProfile {
+                        region: PROFILE_FIELD_UNCHANGED,
+                        country: PROFILE_FIELD_UNCHANGED,
+                        area: 5,
+                        language: PROFILE_FIELD_UNCHANGED,
+                        platform: PROFILE_FIELD_UNCHANGED,
+                        padding: [0; 3],
+                    },
+                })
+                .expect("Should have set my data");
+
+            context
+                .save_my_data_to(&mut archive, "/1/mydata")
+                .expect("Should have saved");
+
+            let raw_data = archive.read("/1/mydata", 0, MY_DATA_FILE_SIZE).unwrap();
+            let saved_my_data = MyData::try_from_le_bytes(&raw_data).unwrap();
+            assert!(!saved_my_data.is_public_mode);
+            assert!(saved_my_data.is_show_game_mode);
+            assert!(!saved_my_data.is_show_played_game);
+            assert_eq!(saved_my_data.personal_comment, "Updated");
+            assert_eq!(saved_my_data.profile.area, 5);
+            assert_eq!(saved_my_data.screen_name, context.my_data.screen_name);
+        }
+
+        #[test]
+        fn should_return_an_error_if_the_file_cant_be_read() {
+            let context = FriendServiceContext::new().unwrap();
+            let mut archive = MemoryArchive::default();
+
+            let result = context.save_my_data_to(&mut archive, "/1/mydata");
+
+            assert!(result.is_err());
+            assert!(!archive.exists("/1/mydata"));
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use alloc::vec::Vec;
+use ctr::frd::{FriendKey, NotificationEvent};
+use no_std_io::{Reader, Writer};
+
+// Event types match ctrulib's NotificationTypes
+pub const FRIEND_UPDATED_PRESENCE_EVENT: u8 = 4;
+pub const FRIEND_SENT_INVITATION_EVENT: u8 = 9;
+
+/// Creates an event with the type in the first byte and the friend key after the padding.
+pub fn create_notification_event(event_type: u8, friend_key: &FriendKey) -> NotificationEvent {
+    let mut bytes = Vec::new();
+    bytes.checked_write_le(0, &event_type);
+    bytes.checked_write_le(8, friend_key);
+    bytes.read_le(0).unwrap_or_default()
+}
+
+pub fn get_notification_event_type(event: &NotificationEvent) -> u8 {
+    let mut bytes = Vec::new();
+    bytes.checked_write_le(0, event);
+    bytes.first().copied().unwrap_or_default()
+}
+
+/// Other local sessions see the user as a friend whose presence was updated,
+/// so the friend key is the user's own.
+pub fn create_self_presence_changed_event(my_friend_key: &FriendKey) -> NotificationEvent {
+    create_notification_event(FRIEND_UPDATED_PRESENCE_EVENT, my_friend_key)
+}
+
+/// The friend key is the sender's, since that's who the invitation is from.
+pub fn create_friend_sent_invitation_event(friend_key: &FriendKey) -> NotificationEvent {
+    create_notification_event(FRIEND_SENT_INVITATION_EVENT, friend_key)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec;
+
+    const FRIEND_KEY: FriendKey = FriendKey {
+        principal_id: 0x11223344,
+        padding: 0,
+        local_friend_code: 0x0011223344,
+    };
+
+    fn get_event_bytes(event: &NotificationEvent) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.checked_write_le(0, event);
+        bytes
+    }
+
+    fn get_friend_key_bytes() -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.checked_write_le(0, &FRIEND_KEY);
+        bytes
+    }
+
+    mod get_notification_event_type {
+        use super::*;
+
+        #[test]
+        fn should_return_the_type_the_event_was_created_with() {
+            let event = create_notification_event(7, &FRIEND_KEY);
+            assert_eq!(get_notification_event_type(&event), 7);
+        }
+    }
+
+    mod create_self_presence_changed_event {
+        use super::*;
+
+        #[test]
+        fn should_have_the_updated_presence_type_and_key() {
+            let bytes = get_event_bytes(&create_self_presence_changed_event(&FRIEND_KEY));
+            assert_eq!(bytes[0], FRIEND_UPDATED_PRESENCE_EVENT);
+            assert_eq!(bytes[8..24], get_friend_key_bytes());
+        }
+    }
+
+    mod create_friend_sent_invitation_event {
+        use super::*;
+
+        #[test]
+        fn should_have_the_invitation_type_and_key() {
+            let bytes = get_event_bytes(&create_friend_sent_invitation_event(&FRIEND_KEY));
+            assert_eq!(bytes[0], FRIEND_SENT_INVITATION_EVENT);
+            assert_eq!(bytes[8..24], get_friend_key_bytes());
+        }
+    }
+}
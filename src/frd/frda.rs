@@ -1,6 +1,24 @@
-use crate::FriendSysmodule;
+use crate::{
+    frd::{
+        context::MyDataUpdate,
+        online_play::{
+            add_friend::{create_add_friend_request, fetch_add_friend},
+            retry::clamp_nasc_attempt_count,
+        },
+        utils,
+    },
+    FriendSysmodule,
+};
+use alloc::vec::Vec;
 use core::convert::From;
-use ctr::{ctr_method, frd::GameKey, res::CtrResult, sysmodule::server::Service};
+use ctr::{
+    ctr_method,
+    frd::{FriendComment, FriendKey, FriendProfile, GameKey},
+    ipc::{Command, Handles, StaticBuffer},
+    res::CtrResult,
+    svc,
+    sysmodule::server::Service,
+};
 use no_std_io::{EndianRead, EndianWrite};
 use num_enum::{FromPrimitive, IntoPrimitive};
 
@@ -64,6 +82,14 @@ pub enum FrdACommand {
     AddFriendWithApproach = 0x34,
     DecryptApproachContext = 0x35,
     GetExtendedNatProperties = 0x36,
+    // Custom, not present in the official sysmodule
+    GetFriendLastOnline = 0x101,
+    GetFriendListCount = 0x102,
+    GetMyPackedPreference = 0x103,
+    ClearEventNotifications = 0x104,
+    GetFriendInfoPage = 0x105,
+    HasDetectedNatProperties = 0x106,
+    GetServiceLocatorPort = 0x107,
 
     // frd:a exclusive
     CreateLocalAccount = 0x401,
@@ -82,6 +108,15 @@ pub enum FrdACommand {
     SetMyNCPrincipalId = 0x40e,
     SetPersonalComment = 0x40f,
     IncrementAccountConfigCounter = 0x410,
+
+    // Custom, not present in the official sysmodule
+    SetFriendOnline = 0x501,
+    ReloadFriendList = 0x502,
+    SetMyPresence = 0x503,
+    GetSessionClientInfo = 0x504,
+    GetAccountConfigCounter = 0x505,
+    GetNascEnvironment = 0x507,
+    SetNascAttemptCount = 0x508,
 }
 
 impl Service for FrdACommand {
@@ -105,14 +140,55 @@ fn create_local_account(
     _input: CreateLocalAccountIn,
 ) -> CtrResult {
     // Stubbed so we don't write actual save data
+    // TODO: Find out how the official sysmodule derives a new account's local friend code
     Ok(())
 }
 
-#[ctr_method(cmd = "FrdACommand::HasUserData", normal = 0x1, translate = 0x0)]
-fn has_user_data(_server: &mut FriendSysmodule, _session_index: usize) -> CtrResult {
+#[derive(EndianRead, EndianWrite)]
+struct AddFriendOnlineIn {
+    principal_id: u32,
+    event_handle: Handles,
+}
+
+#[ctr_method(cmd = "FrdACommand::AddFriendOnline", normal = 0x1, translate = 0x0)]
+fn add_friend_online(
+    server: &mut FriendSysmodule,
+    session_index: usize,
+    input: AddFriendOnlineIn,
+) -> CtrResult {
+    <Command>::validate_header(0x4060042u32)?;
+
+    server.context.ensure_account_configured()?;
+    server.context.check_can_add_friend(&FriendKey {
+        principal_id: input.principal_id,
+        padding: 0,
+        local_friend_code: utils::convert_principal_id_to_friend_code(input.principal_id)?,
+    })?;
+
+    // The friend list app isn't a game, so it has no game id
+    let process_id = server.context.session_contexts[session_index].process_id;
+    let request =
+        create_add_friend_request(&server.context, process_id, 0, 0, 0, input.principal_id)?;
+
+    fetch_add_friend(
+        &mut server.context,
+        session_index,
+        &request,
+        input.principal_id,
+    )?;
+
+    if let Some(handle) = input.event_handle.into_handle() {
+        svc::signal_event(&handle)?;
+    }
+
     Ok(())
 }
 
+#[ctr_method(cmd = "FrdACommand::HasUserData", normal = 0x2, translate = 0x0)]
+fn has_user_data(server: &mut FriendSysmodule, _session_index: usize) -> CtrResult<u32> {
+    Ok(server.context.has_user_data() as u32)
+}
+
 #[ctr_method(cmd = "FrdACommand::SetPresenseGameKey", normal = 0x1, translate = 0x0)]
 fn set_precense_game_key(
     server: &mut FriendSysmodule,
@@ -123,8 +199,220 @@ fn set_precense_game_key(
     Ok(())
 }
 
+#[ctr_method(cmd = "FrdACommand::SetMyFavoriteGame", normal = 0x1, translate = 0x0)]
+fn set_my_favorite_game(
+    server: &mut FriendSysmodule,
+    _session_index: usize,
+    my_favorite_game: GameKey,
+) -> CtrResult {
+    server.context.my_data.my_favorite_game = my_favorite_game;
+    server.context.save_my_data()
+}
+
+/// This layout hasn't been confirmed against the official sysmodule.
+/// The fields are read as the preferences, favorite game, comment, and profile,
+/// each in the layout of its getter.
+#[derive(EndianRead, EndianWrite)]
+struct SetMyDataIn {
+    unk1: u32,
+    unk2: u32,
+    unk3: u32,
+    unk4: GameKey,
+    unk5: FriendComment,
+    padding: u16,
+    unk6: FriendProfile,
+}
+
 #[ctr_method(cmd = "FrdACommand::SetMyData", normal = 0x1, translate = 0x0)]
-fn set_my_data(_server: &mut FriendSysmodule, _session_index: usize) -> CtrResult {
-    // Stubbed so we don't write actual save data
+fn set_my_data(
+    server: &mut FriendSysmodule,
+    _session_index: usize,
+    input: SetMyDataIn,
+) -> CtrResult {
+    <Command>::validate_header(0x40c0480u32)?;
+
+    let personal_comment = utils::decode_comment(&input.unk5);
+    server.context.set_my_data(MyDataUpdate {
+        is_public_mode: input.unk1 != 0,
+        is_show_game_mode: input.unk2 != 0,
+        is_show_played_game: input.unk3 != 0,
+        my_favorite_game: input.unk4,
+        personal_comment: &personal_comment,
+        profile: input.unk6,
+    })?;
+    server.context.save_my_data()
+}
+
+#[ctr_method(cmd = "FrdACommand::SetPersonalComment", normal = 0x1, translate = 0x0)]
+fn set_personal_comment(
+    server: &mut FriendSysmodule,
+    _session_index: usize,
+    comment: FriendComment,
+) -> CtrResult {
+    let personal_comment = utils::decode_comment(&comment);
+    server.context.set_personal_comment(&personal_comment);
+    server.context.save_my_data()
+}
+
+#[ctr_method(
+    cmd = "FrdACommand::IncrementAccountConfigCounter",
+    normal = 0x1,
+    translate = 0x0
+)]
+fn increment_account_config_counter(
+    server: &mut FriendSysmodule,
+    _session_index: usize,
+) -> CtrResult {
+    server.context.increment_account_config_counter();
     Ok(())
 }
+
+#[derive(EndianRead, EndianWrite)]
+struct SetFriendOnlineIn {
+    friend_key: FriendKey,
+    is_online: u32,
+}
+
+#[ctr_method(cmd = "FrdACommand::SetFriendOnline", normal = 0x1, translate = 0x0)]
+fn set_friend_online(
+    server: &mut FriendSysmodule,
+    _session_index: usize,
+    input: SetFriendOnlineIn,
+) -> CtrResult {
+    server
+        .context
+        .set_friend_online(&input.friend_key, input.is_online != 0)?;
+    Ok(())
+}
+
+#[ctr_method(cmd = "FrdACommand::ReloadFriendList", normal = 0x1, translate = 0x0)]
+fn reload_friend_list(server: &mut FriendSysmodule, _session_index: usize) -> CtrResult {
+    server.context.reload_friend_list()
+}
+
+#[ctr_method(cmd = "FrdACommand::SetMyPresence", normal = 0x1, translate = 0x0)]
+fn set_my_presence(
+    server: &mut FriendSysmodule,
+    session_index: usize,
+    presence: StaticBuffer,
+) -> CtrResult {
+    <Command>::validate_header(0x5030002u32)?;
+    <Command>::validate_buffer_id(1, 0)?;
+
+    let raw_presence: Vec<u8> = unsafe { presence.iter::<u8>() }.collect();
+    server.context.set_my_presence(&raw_presence)?;
+    let signaled_session_indexes = server.context.notify_my_presence_changed(session_index);
+    server
+        .context
+        .signal_client_events(&signaled_session_indexes)
+}
+
+#[derive(EndianRead, EndianWrite)]
+struct GetSessionClientInfoOut {
+    len: u32,
+    session_client_infos: StaticBuffer,
+}
+
+#[ctr_method(
+    cmd = "FrdACommand::GetSessionClientInfo",
+    normal = 0x2,
+    translate = 0x2
+)]
+fn get_session_client_info(
+    server: &mut FriendSysmodule,
+    session_index: usize,
+) -> CtrResult<GetSessionClientInfoOut> {
+    let session_client_infos = server.context.get_session_client_infos();
+    let static_buffer = server
+        .context
+        .copy_into_session_static_buffer(session_index, &session_client_infos);
+
+    Ok(GetSessionClientInfoOut {
+        len: session_client_infos.len() as u32,
+        session_client_infos: StaticBuffer::new(static_buffer, 0),
+    })
+}
+
+#[ctr_method(
+    cmd = "FrdACommand::GetAccountConfigCounter",
+    normal = 0x2,
+    translate = 0x0
+)]
+fn get_account_config_counter(
+    server: &mut FriendSysmodule,
+    _session_index: usize,
+) -> CtrResult<u32> {
+    Ok(server.context.counter)
+}
+
+#[ctr_method(cmd = "FrdACommand::GetNascEnvironment", normal = 0x2, translate = 0x0)]
+fn get_nasc_environment(server: &mut FriendSysmodule, _session_index: usize) -> CtrResult<u32> {
+    Ok(server.context.account_config.nasc_environment as u32)
+}
+
+#[ctr_method(
+    cmd = "FrdACommand::SetNascAttemptCount",
+    normal = 0x1,
+    translate = 0x0
+)]
+fn set_nasc_attempt_count(
+    server: &mut FriendSysmodule,
+    _session_index: usize,
+    attempt_count: u32,
+) -> CtrResult {
+    server.context.nasc_attempt_count = clamp_nasc_attempt_count(attempt_count as usize);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frd::harness::create_test_sysmodule;
+
+    mod set_my_favorite_game {
+        use super::*;
+
+        #[test]
+        fn should_update_the_favorite_game() {
+            let mut server = create_test_sysmodule(1);
+            let my_favorite_game = GameKey {
+                title_id: 0x0004000000123400,
+                version: 1,
+                unk: 0,
+            };
+
+            set_my_favorite_game(&mut server, 0, my_favorite_game).unwrap();
+
+            assert_eq!(server.context.my_data.my_favorite_game, my_favorite_game);
+        }
+    }
+
+    mod set_nasc_attempt_count {
+        use super::*;
+        use crate::frd::online_play::retry::MAX_NASC_ATTEMPT_COUNT;
+
+        #[test]
+        fn should_cap_the_attempt_count() {
+            let mut server = create_test_sysmodule(1);
+
+            set_nasc_attempt_count(&mut server, 0, 100).unwrap();
+
+            assert_eq!(server.context.nasc_attempt_count, MAX_NASC_ATTEMPT_COUNT);
+        }
+    }
+
+    mod get_nasc_environment {
+        use super::*;
+        use crate::frd::save::account::NascEnvironment;
+
+        #[test]
+        fn should_return_the_loaded_nasc_environment() {
+            let mut server = create_test_sysmodule(1);
+            server.context.account_config.nasc_environment = NascEnvironment::Test;
+
+            let result = get_nasc_environment(&mut server, 0);
+
+            assert_eq!(result, Ok(1));
+        }
+    }
+}
@@ -2,7 +2,12 @@ use crate::{frd::wifi, FriendSysmodule};
 use alloc::vec;
 use core::convert::From;
 use ctr::{
-    ac::AcController, ctr_method, ipc::Handles, res::CtrResult, svc, sysmodule::server::Service,
+    ac::{acu_get_wifi_status, AcController},
+    ctr_method,
+    ipc::Handles,
+    res::CtrResult,
+    svc,
+    sysmodule::server::Service,
 };
 use num_enum::{FromPrimitive, IntoPrimitive};
 
@@ -15,6 +20,8 @@ pub enum FrdNCommand {
     ConnectToWiFi = 2,
     DisconnectFromWiFi = 3,
     GetWiFiState = 4,
+    // Custom, not present in the official sysmodule
+    GetWiFiStateDetail = 5,
 }
 
 impl Service for FrdNCommand {
@@ -23,6 +30,14 @@ impl Service for FrdNCommand {
     const MAX_SESSION_COUNT: i32 = 1;
 }
 
+/// Checks the real connection status with AC, since the OS can drop the connection underneath us.
+/// NDM waits on the wifi event and polls frd:n, so this runs before each frd:n request.
+pub fn refresh_wifi_connection_status(server: &mut FriendSysmodule) -> CtrResult {
+    let is_connected = acu_get_wifi_status()? != 0;
+    wifi::reconcile_wifi_connection_status(&mut server.context, is_connected);
+    wifi::signal_wifi_state_change(&mut server.context)
+}
+
 #[ctr_method(cmd = "FrdNCommand::GetWiFiEvent", normal = 0x1, translate = 0x2)]
 fn get_wifi_event(server: &mut FriendSysmodule, _session_index: usize) -> CtrResult<Handles> {
     let raw_handle = unsafe { server.context.ndm_wifi_event_handle.get_raw() };
@@ -44,15 +59,26 @@ fn disconnect_from_wifi(
     let next_state = next_state as u8;
     let connection_status = server.context.wifi_connection_status;
     let original_ndm_wifi_state = server.context.ndm_wifi_state;
+    let is_connected = connection_status == wifi::WiFiConnectionStatus::Connected;
+
+    if is_connected {
+        wifi::track_wifi_state_change(&mut server.context);
+    }
+
     server.context.ndm_wifi_state = next_state ^ 1;
 
-    if connection_status == wifi::WiFiConnectionStatus::Connected {
+    if is_connected {
         wifi::set_wifi_connection_status(
             &mut server.context,
             wifi::WiFiConnectionStatus::Disconnecting,
-        )?;
-        AcController::disconnect()?;
-        wifi::set_wifi_connection_status(&mut server.context, wifi::WiFiConnectionStatus::Idle)?;
+        );
+        let disconnect_result = AcController::disconnect();
+        // A failed disconnect stays disconnecting, and clients still hear about it
+        if disconnect_result.is_ok() {
+            wifi::set_wifi_connection_status(&mut server.context, wifi::WiFiConnectionStatus::Idle);
+        }
+        wifi::signal_wifi_state_change(&mut server.context)?;
+        disconnect_result?;
     } else if original_ndm_wifi_state == 2 {
         svc::signal_event(&server.context.ndm_wifi_event_handle)?;
     }
@@ -67,3 +93,14 @@ fn get_wifi_state(server: &mut FriendSysmodule, _session_index: usize) -> CtrRes
         server.context.wifi_connection_status,
     ))
 }
+
+#[ctr_method(cmd = "FrdNCommand::GetWiFiStateDetail", normal = 0x3, translate = 0x0)]
+fn get_wifi_state_detail(
+    server: &mut FriendSysmodule,
+    _session_index: usize,
+) -> CtrResult<wifi::WiFiStateDetail> {
+    Ok(wifi::get_wifi_state_detail(
+        server.context.ndm_wifi_state,
+        server.context.wifi_connection_status,
+    ))
+}
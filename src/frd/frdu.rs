@@ -1,27 +1,33 @@
-use super::{frda::FrdACommand, result::FrdErrorCode, utils};
+use super::{frda::FrdACommand, notification, result::FrdErrorCode, utils};
 use crate::{
     frd::{
         online_play::{
-            authentication::{create_game_login_request, GameAuthenticationData},
-            locate::{create_game_service_locate_request, ServiceLocateData},
+            authentication::{
+                create_game_login_request, fetch_game_authentication, parse_ingamesn,
+                GAME_AUTHENTICATION_TTL, INGAMESN_SIZE,
+            },
+            locate::{
+                create_game_service_locate_request, fetch_service_locator, SERVICE_LOCATOR_TTL,
+            },
+            retry::fetch_with_retry,
         },
-        save::friend_list::MAX_FRIEND_COUNT,
+        save::{friend_list::MAX_FRIEND_COUNT, my_data::MyScreenName},
     },
     FriendSysmodule,
 };
-use alloc::{str, vec, vec::Vec};
+use alloc::vec::Vec;
 use core::{cmp::min, convert::From};
 use ctr::{
     ctr_method,
     frd::{
-        ExpandedFriendPresence, FriendComment, FriendInfo, FriendKey, FriendPresence,
-        FriendProfile, GameKey, Mii, ScrambledFriendCode, ScreenName, TrivialCharacterSet,
+        FriendComment, FriendInfo, FriendKey, FriendPresence, FriendProfile, GameKey, Mii,
+        NotificationEvent, ScrambledFriendCode, ScreenName, TrivialCharacterSet,
     },
     ipc::{BufferRights, Command, CurrentProcessId, Handles, PermissionBuffer, StaticBuffer},
     result::CtrResult,
     svc,
     sysmodule::server::Service,
-    time::calculate_time_difference_from_now,
+    time::SystemTimestamp,
     utils::cstring::parse_null_terminated_str,
 };
 use no_std_io::{Cursor, EndianRead, EndianWrite, StreamContainer, StreamWriter};
@@ -86,6 +92,14 @@ pub enum FrdUCommand {
     AddFriendWithApproach = 0x34,
     DecryptApproachContext = 0x35,
     GetExtendedNatProperties = 0x36,
+    // Custom, not present in the official sysmodule
+    GetFriendLastOnline = 0x101,
+    GetFriendListCount = 0x102,
+    GetMyPackedPreference = 0x103,
+    ClearEventNotifications = 0x104,
+    GetFriendInfoPage = 0x105,
+    HasDetectedNatProperties = 0x106,
+    GetServiceLocatorPort = 0x107,
 }
 
 impl Service for FrdUCommand {
@@ -96,39 +110,39 @@ impl Service for FrdUCommand {
 
 #[ctr_method(cmd = "FrdUCommand::HasLoggedIn", normal = 0x2, translate = 0x0)]
 #[ctr_method(cmd = "FrdACommand::HasLoggedIn", normal = 0x2, translate = 0x0)]
-fn has_logged_in(_server: &mut FriendSysmodule, _session_index: usize) -> CtrResult<u32> {
-    Ok(true as u32)
+fn has_logged_in(server: &mut FriendSysmodule, _session_index: usize) -> CtrResult<u32> {
+    Ok(server.context.logged_in as u32)
 }
 
 #[ctr_method(cmd = "FrdUCommand::IsOnline", normal = 0x2, translate = 0x0)]
 #[ctr_method(cmd = "FrdACommand::IsOnline", normal = 0x2, translate = 0x0)]
-fn is_online(_server: &mut FriendSysmodule, _session_index: usize) -> CtrResult<u32> {
-    Ok(true as u32)
+fn is_online(server: &mut FriendSysmodule, _session_index: usize) -> CtrResult<u32> {
+    Ok(server.context.logged_in as u32)
 }
 
 #[ctr_method(cmd = "FrdUCommand::Login", normal = 0x1, translate = 0x0)]
 #[ctr_method(cmd = "FrdACommand::Login", normal = 0x1, translate = 0x0)]
-fn login(_server: &mut FriendSysmodule, _session_index: usize, event_handle: Handles) -> CtrResult {
-    if let Some(handle) = event_handle.into_handle() {
-        svc::signal_event(&handle)?;
-    }
+fn login(server: &mut FriendSysmodule, _session_index: usize, event_handle: Handles) -> CtrResult {
+    let handle = event_handle
+        .into_handle()
+        .ok_or(FrdErrorCode::InvalidArguments)?;
+
+    server.context.login()?;
+    svc::signal_event(&handle)?;
     Ok(())
 }
 
 #[ctr_method(cmd = "FrdUCommand::Logout", normal = 0x1, translate = 0x0)]
 #[ctr_method(cmd = "FrdACommand::Logout", normal = 0x1, translate = 0x0)]
-fn logout(_server: &mut FriendSysmodule, _session_index: usize) -> CtrResult {
+fn logout(server: &mut FriendSysmodule, session_index: usize) -> CtrResult {
+    server.context.logout(session_index);
     Ok(())
 }
 
 #[ctr_method(cmd = "FrdUCommand::GetMyFriendKey", normal = 0x5, translate = 0x0)]
 #[ctr_method(cmd = "FrdACommand::GetMyFriendKey", normal = 0x5, translate = 0x0)]
 fn get_my_friend_key(server: &mut FriendSysmodule, _session_index: usize) -> CtrResult<FriendKey> {
-    Ok(FriendKey {
-        local_friend_code: server.context.account_config.local_friend_code,
-        padding: 0,
-        principal_id: server.context.account_config.principal_id,
-    })
+    Ok(server.context.get_my_friend_key())
 }
 
 #[derive(EndianRead, EndianWrite)]
@@ -151,6 +165,20 @@ fn get_my_preference(
     })
 }
 
+#[ctr_method(
+    cmd = "FrdUCommand::GetMyPackedPreference",
+    normal = 0x2,
+    translate = 0x0
+)]
+#[ctr_method(
+    cmd = "FrdACommand::GetMyPackedPreference",
+    normal = 0x2,
+    translate = 0x0
+)]
+fn get_my_packed_preference(server: &mut FriendSysmodule, _session_index: usize) -> CtrResult<u32> {
+    Ok(server.context.my_data.get_packed_preference())
+}
+
 #[ctr_method(cmd = "FrdUCommand::GetMyProfile", normal = 0x3, translate = 0x0)]
 #[ctr_method(cmd = "FrdACommand::GetMyProfile", normal = 0x3, translate = 0x0)]
 fn get_my_profile(server: &mut FriendSysmodule, _session_index: usize) -> CtrResult<FriendProfile> {
@@ -160,7 +188,7 @@ fn get_my_profile(server: &mut FriendSysmodule, _session_index: usize) -> CtrRes
 #[ctr_method(cmd = "FrdUCommand::GetMyPresence", normal = 0x1, translate = 0x2)]
 #[ctr_method(cmd = "FrdACommand::GetMyPresence", normal = 0x1, translate = 0x2)]
 fn get_my_presence(server: &mut FriendSysmodule, session_index: usize) -> CtrResult<StaticBuffer> {
-    let presense = ExpandedFriendPresence::default();
+    let presense = server.context.my_online_activity.presence;
     let static_buffer = server
         .context
         .copy_into_session_static_buffer(session_index, &[presense]);
@@ -172,20 +200,8 @@ fn get_my_presence(server: &mut FriendSysmodule, session_index: usize) -> CtrRes
 fn get_my_screen_name(
     server: &mut FriendSysmodule,
     _session_index: usize,
-) -> CtrResult<ScreenName> {
-    let mut screen_name: [u16; 11] = [0; 11];
-    server
-        .context
-        .my_data
-        .screen_name
-        .encode_utf16()
-        .take(10)
-        .enumerate()
-        .for_each(|(index, short)| {
-            screen_name[index] = short;
-        });
-
-    Ok(ScreenName::new(screen_name))
+) -> CtrResult<MyScreenName> {
+    Ok(server.context.my_data.get_my_screen_name())
 }
 
 #[ctr_method(cmd = "FrdUCommand::GetMyMii", normal = 0x19, translate = 0x0)]
@@ -238,26 +254,14 @@ fn get_my_nc_principal_id(server: &mut FriendSysmodule, _session_index: usize) -
 #[ctr_method(cmd = "FrdUCommand::GetMyComment", normal = 0x12, translate = 0x0)]
 #[ctr_method(cmd = "FrdACommand::GetMyComment", normal = 0x12, translate = 0x0)]
 fn get_my_comment(server: &mut FriendSysmodule, _session_index: usize) -> CtrResult<FriendComment> {
-    let mut comment_shorts: [u16; 17] = [0; 17];
-    server
-        .context
-        .my_data
-        .personal_comment
-        .encode_utf16()
-        .take(16)
-        .enumerate()
-        .for_each(|(index, short)| {
-            comment_shorts[index] = short;
-        });
-
+    let comment_shorts = utils::encode_comment(&server.context.my_data.personal_comment);
     Ok(FriendComment::new(comment_shorts))
 }
 
 #[ctr_method(cmd = "FrdUCommand::GetMyPassword", normal = 0x1, translate = 0x2)]
 #[ctr_method(cmd = "FrdACommand::GetMyPassword", normal = 0x1, translate = 0x2)]
 fn get_my_password(server: &mut FriendSysmodule, session_index: usize) -> CtrResult<StaticBuffer> {
-    let c_password =
-        cstr_core::CString::new(server.context.account_config.nex_password.as_bytes())?;
+    let c_password = cstr_core::CString::new(server.context.get_my_password()?.as_bytes())?;
     let c_password_bytes = c_password.to_bytes_with_nul();
 
     let static_buffer = server
@@ -322,7 +326,12 @@ fn get_friend_presence(
     <Command>::validate_buffer_id(2, 0)?;
 
     let max_out_count = min(input.max_out as usize, MAX_FRIEND_COUNT);
-    let result: Vec<FriendPresence> = vec![Default::default(); max_out_count];
+    let friend_keys = utils::read_static_buffer_items::<FriendKey>(&input.friend_keys).into_iter();
+
+    let result: Vec<FriendPresence> = friend_keys
+        .take(max_out_count)
+        .map(|friend_key| server.context.get_friend_presence(&friend_key))
+        .collect();
     let static_buffer = server
         .context
         .copy_into_session_static_buffer(session_index, &result);
@@ -373,7 +382,7 @@ fn get_friend_screen_name(
         friend_key_count,
         min(max_screen_name_out, max_string_language_out),
     );
-    let friend_keys = unsafe { input.friend_keys.iter::<FriendKey>() };
+    let friend_keys = utils::read_static_buffer_items::<FriendKey>(&input.friend_keys).into_iter();
 
     let result_size = max_out_count * core::mem::size_of::<ScreenName>()
         + max_out_count * core::mem::size_of::<TrivialCharacterSet>();
@@ -421,27 +430,29 @@ fn get_friend_mii(
     _session_index: usize,
     mut input: GetFriendMiiIn,
 ) -> CtrResult<PermissionBuffer> {
+    utils::validate_permission_buffer(&input.friend_miis)?;
     <Command>::validate_header(0x140044u32)?;
     <Command>::validate_buffer_id(2, 0)?;
 
-    let friend_keys = unsafe { input.friend_keys.iter::<FriendKey>() };
+    let friend_keys = utils::read_static_buffer_items::<FriendKey>(&input.friend_keys).into_iter();
     let friend_miis_pointer = input.friend_miis.ptr();
-    let friend_miis_len = input.friend_miis.len();
+    let max_out_count =
+        utils::get_max_out_count::<Mii>(input.max_out_count, input.friend_miis.len());
     let mut friend_miis = unsafe { input.friend_miis.as_write_stream() };
-    let max_out_count = min(input.max_out_count as usize, MAX_FRIEND_COUNT);
 
-    friend_keys.take(max_out_count).for_each(|friend_key| {
-        let mii = server
+    let miis = friend_keys.take(max_out_count).map(|friend_key| {
+        server
             .context
             .get_friend_by_friend_key(&friend_key)
             .map(|friend| friend.mii)
-            .unwrap_or_default();
-        friend_miis.checked_write_stream_le(&mii);
+            .unwrap_or_default()
     });
+    // Only report what was written so clients don't read uninitialized bytes
+    let written_len = utils::write_items_le(&mut friend_miis, miis);
 
     Ok(PermissionBuffer::new(
         friend_miis_pointer,
-        friend_miis_len,
+        written_len,
         BufferRights::Write,
     ))
 }
@@ -463,13 +474,13 @@ fn get_friend_profile(
     <Command>::validate_buffer_id(2, 0)?;
 
     let max_out_count = min(input.max_out as usize, MAX_FRIEND_COUNT);
-    let friend_keys = unsafe { input.friend_keys.iter::<FriendKey>() };
+    let friend_keys = utils::read_static_buffer_items::<FriendKey>(&input.friend_keys).into_iter();
 
     let result: Vec<FriendProfile> = friend_keys
         .take(max_out_count)
         .map(
             |friend_key| match server.context.get_friend_by_friend_key(&friend_key) {
-                Some(friend) => friend.friend_profile,
+                Some(friend) => friend.get_profile(),
                 None => Default::default(),
             },
         )
@@ -507,7 +518,7 @@ fn get_friend_relationship(
     <Command>::validate_buffer_id(2, 0)?;
 
     let max_out_count = min(input.max_out as usize, MAX_FRIEND_COUNT);
-    let friend_keys = unsafe { input.friend_keys.iter::<FriendKey>() };
+    let friend_keys = utils::read_static_buffer_items::<FriendKey>(&input.friend_keys).into_iter();
 
     let result: Vec<u8> = friend_keys
         .take(max_out_count)
@@ -551,7 +562,7 @@ fn get_friend_attribute_flags(
     <Command>::validate_buffer_id(2, 0)?;
 
     let max_out_count = min(input.max_out as usize, MAX_FRIEND_COUNT);
-    let friend_keys = unsafe { input.friend_keys.iter::<FriendKey>() };
+    let friend_keys = utils::read_static_buffer_items::<FriendKey>(&input.friend_keys).into_iter();
 
     let result: Vec<u32> = friend_keys
         .take(max_out_count)
@@ -592,21 +603,22 @@ fn get_friend_playing_game(
     _session_index: usize,
     mut input: GetFriendPlayingGameIn,
 ) -> CtrResult<PermissionBuffer> {
+    utils::validate_permission_buffer(&input.game_keys)?;
     <Command>::validate_header(0x180044u32)?;
     <Command>::validate_buffer_id(2, 0)?;
 
-    let max_out_count = min(input.max_out as usize, MAX_FRIEND_COUNT);
+    let max_out_count = utils::get_max_out_count::<GameKey>(input.max_out, input.game_keys.len());
     let game_keys_pointer = input.game_keys.ptr();
     let mut game_keys = unsafe { input.game_keys.as_write_stream() };
 
-    for _ in 0..max_out_count {
-        let game_key = GameKey::default();
-        game_keys.checked_write_stream_le(&game_key);
-    }
+    let written_len = utils::write_items_le(
+        &mut game_keys,
+        (0..max_out_count).map(|_| GameKey::default()),
+    );
 
     Ok(PermissionBuffer::new(
         game_keys_pointer,
-        max_out_count,
+        written_len,
         BufferRights::Write,
     ))
 }
@@ -636,7 +648,7 @@ fn get_friend_favorite_game(
     <Command>::validate_buffer_id(2, 0)?;
 
     let max_out_count = min(input.max_out as usize, MAX_FRIEND_COUNT);
-    let friend_keys = unsafe { input.friend_keys.iter::<FriendKey>() };
+    let friend_keys = utils::read_static_buffer_items::<FriendKey>(&input.friend_keys).into_iter();
 
     let result: Vec<GameKey> = friend_keys
         .take(max_out_count)
@@ -659,41 +671,86 @@ fn get_friend_favorite_game(
 struct GetFriendInfoIn {
     max_out: u32,
     unk1: u32,
-    // TODO: use this to filter some wide characters
+    // Text that can't be shown in this character set is blanked
     character_set: u32,
     friend_keys: StaticBuffer,
     friend_info_out: PermissionBuffer,
 }
 
+/// Writes the friend info for a page of the requested friend keys into the client's buffer.
+fn write_friend_info_page(
+    server: &mut FriendSysmodule,
+    friend_keys: &StaticBuffer,
+    mut friend_info_out: PermissionBuffer,
+    max_out: u32,
+    offset: u32,
+    character_set: u32,
+) -> PermissionBuffer {
+    let friend_keys = utils::read_static_buffer_items::<FriendKey>(friend_keys);
+    let friend_info_out_pointer = friend_info_out.ptr();
+    let max_out_count = utils::get_max_out_count::<FriendInfo>(max_out, friend_info_out.len());
+    let mut friend_info_stream = unsafe { friend_info_out.as_write_stream() };
+
+    let friend_infos = server.context.get_friend_info_page(
+        &friend_keys,
+        offset as usize,
+        max_out_count,
+        character_set,
+    );
+    let written_len = utils::write_items_le(&mut friend_info_stream, friend_infos);
+
+    PermissionBuffer::new(friend_info_out_pointer, written_len, BufferRights::Write)
+}
+
 #[ctr_method(cmd = "FrdUCommand::GetFriendInfo", normal = 0x1, translate = 0x2)]
 #[ctr_method(cmd = "FrdACommand::GetFriendInfo", normal = 0x1, translate = 0x2)]
 fn get_friend_info(
     server: &mut FriendSysmodule,
     _session_index: usize,
-    mut input: GetFriendInfoIn,
+    input: GetFriendInfoIn,
 ) -> CtrResult<PermissionBuffer> {
+    utils::validate_permission_buffer(&input.friend_info_out)?;
     <Command>::validate_header(0x1a00c4u32)?;
     <Command>::validate_buffer_id(4, 0)?;
 
-    let friend_keys = unsafe { input.friend_keys.iter::<FriendKey>() };
-    let friend_info_out_pointer = input.friend_info_out.ptr();
-    let friend_out_len = input.friend_info_out.len();
-    let mut friend_info_out = unsafe { input.friend_info_out.as_write_stream() };
-    let max_out_count = min(input.max_out as usize, MAX_FRIEND_COUNT);
+    Ok(write_friend_info_page(
+        server,
+        &input.friend_keys,
+        input.friend_info_out,
+        input.max_out,
+        0,
+        input.character_set,
+    ))
+}
 
-    friend_keys.take(max_out_count).for_each(|friend_key| {
-        let friend_info = server
-            .context
-            .get_friend_by_friend_key(&friend_key)
-            .map(|friend| FriendInfo::from(*friend))
-            .unwrap_or_default();
-        friend_info_out.checked_write_stream_le(&friend_info);
-    });
+#[derive(EndianRead, EndianWrite)]
+struct GetFriendInfoPageIn {
+    max_out: u32,
+    // The number of friend keys to skip, like GetFriendKeyList
+    offset: u32,
+    character_set: u32,
+    friend_keys: StaticBuffer,
+    friend_info_out: PermissionBuffer,
+}
 
-    Ok(PermissionBuffer::new(
-        friend_info_out_pointer,
-        friend_out_len,
-        BufferRights::Write,
+#[ctr_method(cmd = "FrdUCommand::GetFriendInfoPage", normal = 0x1, translate = 0x2)]
+#[ctr_method(cmd = "FrdACommand::GetFriendInfoPage", normal = 0x1, translate = 0x2)]
+fn get_friend_info_page(
+    server: &mut FriendSysmodule,
+    _session_index: usize,
+    input: GetFriendInfoPageIn,
+) -> CtrResult<PermissionBuffer> {
+    utils::validate_permission_buffer(&input.friend_info_out)?;
+    <Command>::validate_header(0x10500c4u32)?;
+    <Command>::validate_buffer_id(4, 0)?;
+
+    Ok(write_friend_info_page(
+        server,
+        &input.friend_keys,
+        input.friend_info_out,
+        input.max_out,
+        input.offset,
+        input.character_set,
     ))
 }
 
@@ -712,11 +769,7 @@ fn is_included_in_friend_list(
     _session_index: usize,
     friend_code: u64,
 ) -> CtrResult<u32> {
-    let has_friend = server
-        .context
-        .friend_list
-        .iter()
-        .any(|friend| friend.friend_key.local_friend_code == friend_code);
+    let has_friend = server.context.is_included_in_friend_list(friend_code);
 
     Ok(has_friend as u32)
 }
@@ -727,6 +780,19 @@ struct UnscrambleLocalFriendCodeIn {
     scrambled_friend_codes: StaticBuffer,
 }
 
+/// Reads up to `max_out` codes, never more than the buffer actually holds.
+fn read_scrambled_friend_codes(buffer: &StaticBuffer, max_out: usize) -> Vec<ScrambledFriendCode> {
+    let code_count = min(
+        min(max_out, MAX_FRIEND_COUNT),
+        utils::get_static_buffer_item_count::<ScrambledFriendCode>(buffer),
+    );
+
+    // The count is bounded by the buffer length, so this never reads past its end
+    unsafe { buffer.iter::<ScrambledFriendCode>() }
+        .take(code_count)
+        .collect()
+}
+
 #[ctr_method(
     cmd = "FrdUCommand::UnscrambleLocalFriendCode",
     normal = 0x1,
@@ -745,25 +811,16 @@ fn unscramble_local_friend_code(
     <Command>::validate_header(0x1c0042u32)?;
     <Command>::validate_buffer_id(2, 1)?;
 
-    let max_out_count = min(input.max_out as usize, MAX_FRIEND_COUNT);
     let scrambled_friend_codes =
-        unsafe { input.scrambled_friend_codes.iter::<ScrambledFriendCode>() };
+        read_scrambled_friend_codes(&input.scrambled_friend_codes, input.max_out as usize);
 
     let result: Vec<u64> = scrambled_friend_codes
-        .take(max_out_count)
+        .iter()
         .map(|scrambed_friend_code| {
-            let friend_code = scrambed_friend_code.get_unscrambled_friend_code();
-            let is_in_friend_list = server
-                .context
-                .friend_list
-                .iter()
-                .any(|friend| friend.friend_key.local_friend_code == friend_code);
-
-            if is_in_friend_list {
-                friend_code
-            } else {
-                0
-            }
+            server.context.get_unscrambled_local_friend_code(
+                scrambed_friend_code.get_unscrambled_friend_code(),
+                cfg!(feature = "unscramble-non-friends"),
+            )
         })
         .collect();
 
@@ -784,7 +841,16 @@ fn unscramble_local_friend_code(
     normal = 0x1,
     translate = 0x0
 )]
-fn update_game_mode_description(_server: &mut FriendSysmodule, _session_index: usize) -> CtrResult {
+fn update_game_mode_description(
+    server: &mut FriendSysmodule,
+    _session_index: usize,
+    description: StaticBuffer,
+) -> CtrResult {
+    <Command>::validate_header(0x1d0002u32)?;
+    <Command>::validate_buffer_id(1, 0)?;
+
+    let description: Vec<u8> = unsafe { description.iter::<u8>() }.collect();
+    server.context.set_game_mode_description(&description)?;
     Ok(())
 }
 
@@ -794,10 +860,35 @@ fn update_game_mode(_server: &mut FriendSysmodule, _session_index: usize) -> Ctr
     Ok(0xc4e1)
 }
 
+#[derive(EndianRead, EndianWrite)]
+struct SendInvitationIn {
+    friend_key_count: u32,
+    friend_keys: StaticBuffer,
+}
+
 #[ctr_method(cmd = "FrdUCommand::SendInvitation", normal = 0x1, translate = 0x0)]
 #[ctr_method(cmd = "FrdACommand::SendInvitation", normal = 0x1, translate = 0x0)]
-fn send_invitation(_server: &mut FriendSysmodule, _session_index: usize) -> CtrResult {
-    Ok(())
+fn send_invitation(
+    server: &mut FriendSysmodule,
+    session_index: usize,
+    input: SendInvitationIn,
+) -> CtrResult {
+    <Command>::validate_header(0x1f0042u32)?;
+    <Command>::validate_buffer_id(2, 0)?;
+
+    let friend_key_count = min(input.friend_key_count as usize, MAX_FRIEND_COUNT);
+    let friend_keys: Vec<FriendKey> =
+        utils::read_static_buffer_items::<FriendKey>(&input.friend_keys)
+            .into_iter()
+            .take(friend_key_count)
+            .collect();
+
+    let signaled_session_indexes = server
+        .context
+        .send_invitation(session_index, &friend_keys)?;
+    server
+        .context
+        .signal_client_events(&signaled_session_indexes)
 }
 
 #[ctr_method(
@@ -832,9 +923,9 @@ fn attach_to_event_notification(
 fn set_notification_mask(
     server: &mut FriendSysmodule,
     session_index: usize,
-    notifixation_mask: u32,
+    notification_mask: u32,
 ) -> CtrResult {
-    server.context.session_contexts[session_index].notification_mask = notifixation_mask;
+    server.context.session_contexts[session_index].set_notification_mask(notification_mask);
     Ok(())
 }
 
@@ -846,6 +937,7 @@ struct GetEventNotificationIn {
 
 #[derive(EndianRead, EndianWrite)]
 struct GetEventNotificationOut {
+    // Set when the queue was full and older events were dropped
     unk: u32,
     out_len: u32,
     notifications: PermissionBuffer,
@@ -866,31 +958,53 @@ fn get_event_notification(
     session_index: usize,
     mut input: GetEventNotificationIn,
 ) -> CtrResult<GetEventNotificationOut> {
+    utils::validate_permission_buffer(&input.notifications_out)?;
     <Command>::validate_header(0x220042u32)?;
 
-    let max_notification_count = min(input.max_out as usize, MAX_FRIEND_COUNT);
     let notification_out_pointer = input.notifications_out.ptr();
+    let max_notification_count =
+        utils::get_max_out_count::<NotificationEvent>(input.max_out, input.notifications_out.len());
     let mut notification_out = unsafe { input.notifications_out.as_write_stream() };
 
     let client_event_queue = &mut server.context.session_contexts[session_index].client_event_queue;
+    let notification_count = min(client_event_queue.len(), max_notification_count);
 
-    for notification in client_event_queue.iter().take(max_notification_count) {
-        notification_out.checked_write_stream_le(notification);
-    }
-
-    client_event_queue.clear();
+    // Events that don't fit stay queued for the next call
+    let written_len = utils::write_items_le(
+        &mut notification_out,
+        client_event_queue.drain(notification_count),
+    );
+    let has_dropped_events = client_event_queue.take_dropped_flag();
 
     Ok(GetEventNotificationOut {
-        unk: 0,
-        out_len: max_notification_count as u32,
+        unk: has_dropped_events as u32,
+        out_len: notification_count as u32,
         notifications: PermissionBuffer::new(
             notification_out_pointer,
-            max_notification_count,
+            written_len,
             BufferRights::Write,
         ),
     })
 }
 
+#[ctr_method(
+    cmd = "FrdUCommand::ClearEventNotifications",
+    normal = 0x1,
+    translate = 0x0
+)]
+#[ctr_method(
+    cmd = "FrdACommand::ClearEventNotifications",
+    normal = 0x1,
+    translate = 0x0
+)]
+fn clear_event_notifications(server: &mut FriendSysmodule, session_index: usize) -> CtrResult {
+    // Lets a client reset its notifications without reading them all through GetEventNotification
+    server.context.session_contexts[session_index]
+        .client_event_queue
+        .clear();
+    Ok(())
+}
+
 #[ctr_method(
     cmd = "FrdUCommand::GetLastResponseResult",
     normal = 0x1,
@@ -901,7 +1015,8 @@ fn get_event_notification(
     normal = 0x1,
     translate = 0x0
 )]
-fn get_last_response_result(_server: &mut FriendSysmodule, _session_index: usize) -> CtrResult {
+fn get_last_response_result(server: &mut FriendSysmodule, session_index: usize) -> CtrResult {
+    server.context.session_contexts[session_index].last_response_result?;
     Ok(())
 }
 
@@ -980,7 +1095,7 @@ fn result_to_error_code(
 #[derive(EndianRead, EndianWrite)]
 struct RequestGameAuthenticationDataIn {
     requesting_game_id: u32,
-    ingamesn_bytes: [u8; 24],
+    ingamesn_bytes: [u8; INGAMESN_SIZE],
     sdk_version_low: u32,
     sdk_version_high: u32,
     requesting_process_id: CurrentProcessId,
@@ -1004,29 +1119,25 @@ fn request_game_authentication(
 ) -> CtrResult {
     <Command>::validate_header(0x280244u32)?;
 
-    let request = create_game_login_request(
-        &server.context,
-        input.requesting_process_id.raw(),
-        input.requesting_game_id,
-        input.sdk_version_low as u8,
-        input.sdk_version_high as u8,
-        parse_null_terminated_str(&input.ingamesn_bytes),
+    server.context.ensure_account_configured()?;
+    let ingamesn = parse_ingamesn(&input.ingamesn_bytes)?;
+
+    fetch_with_retry(
+        &mut server.context,
+        session_index,
+        |context| {
+            create_game_login_request(
+                context,
+                input.requesting_process_id.raw(),
+                input.requesting_game_id,
+                input.sdk_version_low as u8,
+                input.sdk_version_high as u8,
+                ingamesn,
+            )
+        },
+        fetch_game_authentication,
     )?;
 
-    let mut buffer: [u8; 312] = [0; 312];
-    request.download_data_into_buffer(&mut buffer)?;
-
-    let response_status_code = request.get_response_status_code()?;
-    let buffer_str = str::from_utf8(&buffer)?
-        .trim_end_matches(char::from(0))
-        .trim_end_matches("\r\n");
-
-    let authentication_response =
-        GameAuthenticationData::from_fetched_response(buffer_str, response_status_code)?;
-
-    server.context.session_contexts[session_index].last_game_authentication_response =
-        Some(authentication_response);
-
     if let Some(handle) = input.event_handle.into_handle() {
         svc::signal_event(&handle)?;
     }
@@ -1048,10 +1159,16 @@ fn get_game_authentication_data(
     server: &mut FriendSysmodule,
     session_index: usize,
 ) -> CtrResult<StaticBuffer> {
-    let last_game_authentication_response =
-        server.context.session_contexts[session_index].last_game_authentication_response;
+    let game_auth_response = server.context.session_contexts[session_index]
+        .last_game_authentication_response
+        .ok_or(FrdErrorCode::MissingData)?;
 
-    let game_auth_data = last_game_authentication_response.ok_or(FrdErrorCode::MissingData)?;
+    // Game servers reject old tokens, so the client is told to log in again.
+    // The age is from when the token was fetched, since the user set console clock
+    // can't be compared with the datetime the server sent.
+    let game_auth_data = game_auth_response
+        .get_if_fresh(GAME_AUTHENTICATION_TTL)
+        .ok_or(FrdErrorCode::GameAuthenticationExpired)?;
 
     let static_buffer = server
         .context
@@ -1088,35 +1205,25 @@ fn request_service_locator(
 ) -> CtrResult {
     <Command>::validate_header(0x2a0204u32)?;
 
-    let request = create_game_service_locate_request(
-        &server.context,
-        input.requesting_process_id.raw(),
-        input.requesting_game_id,
-        input.sdk_version_low as u8,
-        input.sdk_version_high as u8,
-        parse_null_terminated_str(&input.key_hash_bytes),
-        parse_null_terminated_str(&input.svc_bytes),
+    server.context.ensure_account_configured()?;
+
+    fetch_with_retry(
+        &mut server.context,
+        session_index,
+        |context| {
+            create_game_service_locate_request(
+                context,
+                input.requesting_process_id.raw(),
+                input.requesting_game_id,
+                input.sdk_version_low as u8,
+                input.sdk_version_high as u8,
+                parse_null_terminated_str(&input.key_hash_bytes),
+                parse_null_terminated_str(&input.svc_bytes),
+            )
+        },
+        fetch_service_locator,
     )?;
 
-    let mut buffer: [u8; 312] = [0; 312];
-    request.download_data_into_buffer(&mut buffer)?;
-
-    let response_status_code = request.get_response_status_code()?;
-    let buffer_str = str::from_utf8(&buffer)?
-        .trim_end_matches(char::from(0))
-        .trim_end_matches("\r\n");
-
-    let service_locator_response =
-        ServiceLocateData::from_fetched_response(buffer_str, response_status_code)?;
-
-    server.context.session_contexts[session_index].last_service_locator_response =
-        Some(service_locator_response);
-
-    let service_locator_timestamp = service_locator_response.timestamp.get_unix_timestamp();
-
-    server.context.session_contexts[session_index].server_time_interval =
-        calculate_time_difference_from_now(service_locator_timestamp);
-
     if let Some(handle) = input.event_handle.into_handle() {
         svc::signal_event(&handle)?;
     }
@@ -1141,7 +1248,10 @@ fn get_service_locator_data(
     let service_locator_response =
         server.context.session_contexts[session_index].last_service_locator_response;
 
-    let service_locate_data = service_locator_response.ok_or(FrdErrorCode::MissingData)?;
+    // NASC tokens expire, so treat old responses as missing
+    let service_locate_data = service_locator_response
+        .and_then(|response| response.get_if_fresh(SERVICE_LOCATOR_TTL))
+        .ok_or(FrdErrorCode::MissingData)?;
 
     let static_buffer = server
         .context
@@ -1161,18 +1271,22 @@ fn get_service_locator_data(
     translate = 0x0
 )]
 fn detect_nat_properties(
-    _server: &mut FriendSysmodule,
+    server: &mut FriendSysmodule,
     _session_index: usize,
     event_handles: Handles,
 ) -> CtrResult {
+    // The detection isn't implemented, so it always finds the default properties
+    server
+        .context
+        .set_detected_nat_properties(Default::default());
+
     // Normally this should only signal once nat properties are fetched,
     // but we're not building online functionality at the moment, so
     // we'll signal it immediately.
-    for event_handle in event_handles.into_handles().iter() {
-        svc::signal_event(event_handle).unwrap();
-    }
-
-    Ok(())
+    event_handles
+        .into_handles()
+        .iter()
+        .try_for_each(svc::signal_event)
 }
 
 #[derive(EndianRead, EndianWrite)]
@@ -1204,13 +1318,18 @@ fn get_nat_properties(
     normal = 0x3,
     translate = 0x0
 )]
-fn get_server_time_interval(server: &mut FriendSysmodule, session_index: usize) -> CtrResult<u64> {
+fn get_server_time_interval(server: &mut FriendSysmodule, session_index: usize) -> CtrResult<i64> {
     Ok(server.context.session_contexts[session_index].server_time_interval)
 }
 
 #[ctr_method(cmd = "FrdUCommand::AllowHalfAwake", normal = 0x1, translate = 0x0)]
 #[ctr_method(cmd = "FrdACommand::AllowHalfAwake", normal = 0x1, translate = 0x0)]
-fn allow_half_awake(_server: &mut FriendSysmodule, _session_index: usize) -> CtrResult {
+fn allow_half_awake(
+    _server: &mut FriendSysmodule,
+    _session_index: usize,
+    half_awake_allowed: u32,
+) -> CtrResult {
+    notification::set_half_awake_allowed(half_awake_allowed != 0);
     Ok(())
 }
 
@@ -1227,10 +1346,13 @@ fn get_server_types(
     server: &mut FriendSysmodule,
     _session_index: usize,
 ) -> CtrResult<GetServerTypesOut> {
+    let (nasc_environment, server_type_1, server_type_2) =
+        server.context.account_config.get_server_types()?;
+
     Ok(GetServerTypesOut {
-        nasc_environment: server.context.account_config.nasc_environment as u32,
-        server_type_1: server.context.account_config.server_type_1 as u32,
-        server_type_2: server.context.account_config.server_type_2 as u32,
+        nasc_environment: nasc_environment as u32,
+        server_type_1: server_type_1 as u32,
+        server_type_2: server_type_2 as u32,
     })
 }
 
@@ -1252,7 +1374,7 @@ fn get_friend_comment(
     <Command>::validate_buffer_id(3, 0)?;
 
     let friend_key_count = min(input.max_count as usize, MAX_FRIEND_COUNT);
-    let friend_keys = unsafe { input.friend_keys.iter::<FriendKey>() };
+    let friend_keys = utils::read_static_buffer_items::<FriendKey>(&input.friend_keys).into_iter();
 
     let result: Vec<FriendComment> = friend_keys
         .take(friend_key_count)
@@ -1370,3 +1492,461 @@ fn get_extended_nat_properties(
         unk3: nat_properties.get_unk3() as u32,
     })
 }
+
+#[ctr_method(
+    cmd = "FrdUCommand::HasDetectedNatProperties",
+    normal = 0x2,
+    translate = 0x0
+)]
+#[ctr_method(
+    cmd = "FrdACommand::HasDetectedNatProperties",
+    normal = 0x2,
+    translate = 0x0
+)]
+fn has_detected_nat_properties(
+    server: &mut FriendSysmodule,
+    _session_index: usize,
+) -> CtrResult<u32> {
+    // GetNatProperties returns 0 both before a detection and for detected properties of 0
+    Ok(server.context.has_detected_nat_properties as u32)
+}
+
+#[ctr_method(
+    cmd = "FrdUCommand::GetServiceLocatorPort",
+    normal = 0x2,
+    translate = 0x0
+)]
+#[ctr_method(
+    cmd = "FrdACommand::GetServiceLocatorPort",
+    normal = 0x2,
+    translate = 0x0
+)]
+fn get_service_locator_port(server: &mut FriendSysmodule, session_index: usize) -> CtrResult<u32> {
+    let service_locator_response =
+        server.context.session_contexts[session_index].last_service_locator_response;
+
+    // GetServiceLocatorData passes svc_host through as is, so this is for clients that want the port
+    let service_locate_data = service_locator_response
+        .and_then(|response| response.get_if_fresh(SERVICE_LOCATOR_TTL))
+        .ok_or(FrdErrorCode::MissingData)?;
+
+    Ok(service_locate_data.parse_port())
+}
+
+#[derive(EndianRead, EndianWrite)]
+struct GetFriendLastOnlineIn {
+    max_out: u32,
+    friend_keys: StaticBuffer,
+}
+
+#[ctr_method(
+    cmd = "FrdUCommand::GetFriendLastOnline",
+    normal = 0x1,
+    translate = 0x2
+)]
+#[ctr_method(
+    cmd = "FrdACommand::GetFriendLastOnline",
+    normal = 0x1,
+    translate = 0x2
+)]
+fn get_friend_last_online(
+    server: &mut FriendSysmodule,
+    session_index: usize,
+    input: GetFriendLastOnlineIn,
+) -> CtrResult<StaticBuffer> {
+    <Command>::validate_header(0x1010042u32)?;
+    <Command>::validate_buffer_id(2, 0)?;
+
+    let max_out_count = min(input.max_out as usize, MAX_FRIEND_COUNT);
+    let friend_keys = utils::read_static_buffer_items::<FriendKey>(&input.friend_keys).into_iter();
+
+    let result: Vec<SystemTimestamp> = friend_keys
+        .take(max_out_count)
+        .map(|friend_key| server.context.get_friend_last_online(&friend_key))
+        .collect();
+
+    let static_buffer = server
+        .context
+        .copy_into_session_static_buffer(session_index, &result);
+
+    Ok(StaticBuffer::new(static_buffer, 0))
+}
+
+#[ctr_method(cmd = "FrdUCommand::GetFriendListCount", normal = 0x2, translate = 0x0)]
+#[ctr_method(cmd = "FrdACommand::GetFriendListCount", normal = 0x2, translate = 0x0)]
+fn get_friend_list_count(server: &mut FriendSysmodule, _session_index: usize) -> CtrResult<u32> {
+    Ok(server.context.friend_list.len() as u32)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frd::{
+        context::mock::{MOCK_FRIEND_KEY, MOCK_LOCAL_FRIEND_CODE, MOCK_PRINCIPAL_ID},
+        harness::{create_test_sysmodule, send_request, write_command_output},
+    };
+
+    mod get_my_friend_key {
+        use super::*;
+
+        #[derive(EndianRead)]
+        struct GetMyFriendKeyReply {
+            result_code: u32,
+            friend_key: FriendKey,
+        }
+
+        #[test]
+        fn should_write_the_friend_key_layout() {
+            let mut server = create_test_sysmodule(1);
+
+            let reply: GetMyFriendKeyReply =
+                send_request(&mut server, 0, FrdUCommand::GetMyFriendKey, ());
+
+            let mut expected_bytes = [0; 16];
+            expected_bytes[..4].copy_from_slice(&MOCK_PRINCIPAL_ID.to_le_bytes());
+            expected_bytes[8..].copy_from_slice(&MOCK_LOCAL_FRIEND_CODE.to_le_bytes());
+            assert_eq!(reply.result_code, 0);
+            assert_eq!(write_command_output(&reply.friend_key), expected_bytes);
+        }
+    }
+
+    mod get_my_screen_name {
+        use super::*;
+        use alloc::string::ToString;
+
+        #[test]
+        fn should_write_the_name_as_little_endian_utf16() {
+            let mut server = create_test_sysmodule(1);
+            // Both bytes of each character are non-zero, so a byte swap can't go unnoticed
+            server.context.my_data.screen_name = "\u{3042}\u{3044}".to_string();
+
+            let output = get_my_screen_name(&mut server, 0).unwrap();
+
+            let output_bytes = write_command_output(&output);
+            assert_eq!(output_bytes[..6], [0x42, 0x30, 0x44, 0x30, 0x00, 0x00]);
+        }
+    }
+
+    mod get_my_favorite_game {
+        use super::*;
+
+        #[test]
+        fn should_return_the_favorite_game_without_the_unknown_field() {
+            let mut server = create_test_sysmodule(1);
+            server.context.my_data.my_favorite_game = GameKey {
+                title_id: 0x0004000000123400,
+                version: 1,
+                unk: 0xaa,
+            };
+
+            let result = get_my_favorite_game(&mut server, 0);
+
+            assert_eq!(
+                result,
+                Ok(GameKey {
+                    title_id: 0x0004000000123400,
+                    version: 1,
+                    unk: 0,
+                })
+            );
+        }
+    }
+
+    mod get_game_authentication_data {
+        use super::*;
+        use crate::frd::online_play::{
+            authentication::GameAuthenticationData, cache::CachedResponse,
+            utils::get_current_unix_timestamp,
+        };
+
+        #[test]
+        fn should_return_missing_data_before_a_login() {
+            let mut server = create_test_sysmodule(1);
+
+            let result = get_game_authentication_data(&mut server, 0);
+
+            assert_eq!(
+                result.map(|_| ()),
+                Err(FrdErrorCode::MissingData.into_result_code())
+            );
+        }
+
+        #[test]
+        fn should_return_a_token_fetched_just_now_whatever_its_datetime() {
+            let mut server = create_test_sysmodule(1);
+            // Issued 2021-01-02 03:04:05
+            let game_auth_data = GameAuthenticationData::from_fetched_response(
+                "returncd=MDAx&datetime=MjAyMTAxMDIwMzA0MDU*",
+                200,
+            )
+            .unwrap();
+            server.context.session_contexts[0].last_game_authentication_response =
+                Some(CachedResponse::new(game_auth_data));
+
+            let result = get_game_authentication_data(&mut server, 0);
+
+            assert!(result.is_ok());
+        }
+
+        #[test]
+        fn should_return_an_expired_error_for_a_token_fetched_too_long_ago() {
+            let mut server = create_test_sysmodule(1);
+            server.context.session_contexts[0].last_game_authentication_response =
+                Some(CachedResponse {
+                    data: Default::default(),
+                    fetched_at: get_current_unix_timestamp() - GAME_AUTHENTICATION_TTL - 1,
+                });
+
+            let result = get_game_authentication_data(&mut server, 0);
+
+            assert_eq!(
+                result.map(|_| ()),
+                Err(FrdErrorCode::GameAuthenticationExpired.into_result_code())
+            );
+        }
+    }
+
+    mod has_detected_nat_properties {
+        use super::*;
+
+        #[test]
+        fn should_only_be_set_once_a_detection_finishes() {
+            let mut server = create_test_sysmodule(1);
+            assert_eq!(has_detected_nat_properties(&mut server, 0), Ok(0));
+
+            server
+                .context
+                .set_detected_nat_properties(Default::default());
+
+            assert_eq!(has_detected_nat_properties(&mut server, 0), Ok(1));
+        }
+    }
+
+    mod get_service_locator_port {
+        use super::*;
+        use crate::frd::online_play::{cache::CachedResponse, locate::ServiceLocateData};
+
+        #[test]
+        fn should_return_missing_data_before_a_service_locate() {
+            let mut server = create_test_sysmodule(1);
+
+            let result = get_service_locator_port(&mut server, 0);
+
+            assert_eq!(result, Err(FrdErrorCode::MissingData.into_result_code()));
+        }
+
+        #[test]
+        fn should_return_the_port_of_the_svc_host() {
+            let mut server = create_test_sysmodule(1);
+            // svchost is "example.com:443"
+            let service_locate_data = ServiceLocateData::from_fetched_response(
+                "returncd=MDA3&svchost=ZXhhbXBsZS5jb206NDQz&datetime=MjAyMTAxMDIwMzA0MDU*",
+                200,
+            )
+            .unwrap();
+            server.context.session_contexts[0].last_service_locator_response =
+                Some(CachedResponse::new(service_locate_data));
+
+            let result = get_service_locator_port(&mut server, 0);
+
+            assert_eq!(result, Ok(443));
+        }
+    }
+
+    mod allow_half_awake {
+        use super::*;
+        use crate::frd::notification::{
+            get_current_sleep_wifi_action, HalfAwakeTestGuard, SleepWiFiAction,
+        };
+
+        #[test]
+        fn should_change_what_the_sleep_handler_does_with_wifi() {
+            let _guard = HalfAwakeTestGuard::lock();
+            let mut server = create_test_sysmodule(1);
+
+            allow_half_awake(&mut server, 0, 0).unwrap();
+            assert_eq!(get_current_sleep_wifi_action(), SleepWiFiAction::Disconnect);
+
+            allow_half_awake(&mut server, 0, 1).unwrap();
+            assert_eq!(
+                get_current_sleep_wifi_action(),
+                SleepWiFiAction::KeepConnected
+            );
+        }
+    }
+
+    mod logout {
+        use super::*;
+        use crate::frd::online_play::cache::CachedResponse;
+
+        #[test]
+        fn should_make_game_authentication_data_missing() {
+            let mut server = create_test_sysmodule(1);
+            server.context.session_contexts[0].last_game_authentication_response =
+                Some(CachedResponse::new(Default::default()));
+
+            logout(&mut server, 0).unwrap();
+            let result = get_game_authentication_data(&mut server, 0);
+
+            assert_eq!(
+                result.map(|_| ()),
+                Err(FrdErrorCode::MissingData.into_result_code())
+            );
+        }
+    }
+
+    mod read_scrambled_friend_codes {
+        use super::*;
+        use alloc::vec;
+
+        #[test]
+        fn should_stop_at_the_end_of_the_buffer_when_max_out_is_larger() {
+            let bytes = vec![0u8; core::mem::size_of::<ScrambledFriendCode>() * 2];
+            let buffer = StaticBuffer::new(&bytes, 0);
+
+            let result = read_scrambled_friend_codes(&buffer, 10);
+
+            assert_eq!(result.len(), 2);
+        }
+
+        #[test]
+        fn should_stop_at_max_out() {
+            let bytes = vec![0u8; core::mem::size_of::<ScrambledFriendCode>() * 2];
+            let buffer = StaticBuffer::new(&bytes, 0);
+
+            let result = read_scrambled_friend_codes(&buffer, 1);
+
+            assert_eq!(result.len(), 1);
+        }
+    }
+
+    mod get_friend_mii {
+        use super::*;
+
+        #[test]
+        fn should_return_an_error_for_a_null_buffer() {
+            let mut server = create_test_sysmodule(1);
+            let input = GetFriendMiiIn {
+                max_out_count: 1,
+                friend_keys: StaticBuffer::new(&[], 0),
+                friend_miis: PermissionBuffer::new(
+                    core::ptr::null_mut(),
+                    core::mem::size_of::<Mii>(),
+                    BufferRights::Write,
+                ),
+            };
+
+            let result = get_friend_mii(&mut server, 0, input);
+
+            assert_eq!(
+                result.map(|_| ()),
+                Err(FrdErrorCode::InvalidPointer.into_result_code())
+            );
+        }
+    }
+
+    mod clear_event_notifications {
+        use super::*;
+        use crate::frd::context::create_friend_sent_invitation_event;
+
+        #[test]
+        fn should_empty_only_the_session_event_queue() {
+            let mut server = create_test_sysmodule(2);
+            for session_index in 0..2 {
+                server.context.session_contexts[session_index]
+                    .client_event_queue
+                    .push(create_friend_sent_invitation_event(&MOCK_FRIEND_KEY));
+            }
+
+            clear_event_notifications(&mut server, 0).unwrap();
+
+            assert!(server.context.session_contexts[0]
+                .client_event_queue
+                .is_empty());
+            assert_eq!(
+                server.context.session_contexts[1].client_event_queue.len(),
+                1
+            );
+        }
+    }
+
+    mod get_my_presence {
+        use super::*;
+        use crate::frd::context::GAME_MODE_DESCRIPTION_OFFSET;
+
+        #[test]
+        fn should_include_the_game_mode_description() {
+            let mut server = create_test_sysmodule(1);
+            let description = [0x4d, 0x00, 0x6f, 0x00, 0x64, 0x00, 0x65, 0x00];
+            server
+                .context
+                .set_game_mode_description(&description)
+                .unwrap();
+
+            get_my_presence(&mut server, 0).unwrap();
+
+            let static_buffer = &server.context.session_contexts[0].static_buffer;
+            assert_eq!(
+                &static_buffer[GAME_MODE_DESCRIPTION_OFFSET
+                    ..GAME_MODE_DESCRIPTION_OFFSET + description.len()],
+                &description
+            );
+        }
+    }
+
+    mod get_friend_list_count {
+        use super::*;
+
+        #[test]
+        fn should_return_the_number_of_friends() {
+            let mut server = create_test_sysmodule(1);
+
+            let result = get_friend_list_count(&mut server, 0);
+
+            assert_eq!(result, Ok(1));
+        }
+    }
+
+    mod get_friend_key_list {
+        use super::*;
+
+        #[derive(EndianRead)]
+        struct GetFriendKeyListReply {
+            result_code: u32,
+            output: GetFriendKeyListOut,
+        }
+
+        fn send_get_friend_key_list(
+            server: &mut FriendSysmodule,
+            input: GetFriendKeyListIn,
+        ) -> GetFriendKeyListReply {
+            send_request(server, 0, FrdUCommand::GetFriendKeyList, input)
+        }
+
+        #[test]
+        fn should_copy_the_friend_keys_into_the_session_static_buffer() {
+            let mut server = create_test_sysmodule(1);
+
+            let reply =
+                send_get_friend_key_list(&mut server, GetFriendKeyListIn { offset: 0, max: 10 });
+
+            assert_eq!(reply.result_code, 0);
+            assert_eq!(reply.output.len, 1);
+            assert_eq!(
+                server.context.session_contexts[0].static_buffer,
+                write_command_output(&MOCK_FRIEND_KEY)
+            );
+        }
+
+        #[test]
+        fn should_return_nothing_past_the_end_of_the_friend_list() {
+            let mut server = create_test_sysmodule(1);
+
+            let reply =
+                send_get_friend_key_list(&mut server, GetFriendKeyListIn { offset: 5, max: 10 });
+
+            assert_eq!(reply.result_code, 0);
+            assert_eq!(reply.output.len, 0);
+        }
+    }
+}
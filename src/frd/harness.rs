@@ -0,0 +1,46 @@
+use crate::FriendSysmodule;
+use alloc::vec::Vec;
+use ctr::{
+    ipc::Command,
+    sysmodule::server::{Service, ServiceRouter},
+};
+use no_std_io::{EndianRead, EndianWrite, Writer};
+
+/// Creates a sysmodule backed by the mock context, with the given number of open sessions,
+/// so command handlers can be called on the host.
+pub fn create_test_sysmodule(session_count: usize) -> FriendSysmodule {
+    let mut server = FriendSysmodule::new();
+
+    for session_index in 0..session_count {
+        server.context.accept_session(session_index);
+    }
+
+    server
+}
+
+/// Writes a request to the command buffer and dispatches it through `handle_request`,
+/// the same way the service manager does, so tests cover the command routing.
+///
+/// The reply is read back from the command buffer, so it starts with the result code.
+pub fn send_request<C: Service + Into<u16>, I: EndianWrite, O: EndianRead>(
+    server: &mut FriendSysmodule,
+    session_index: usize,
+    command: C,
+    input: I,
+) -> O {
+    Command::new(command, input).write();
+    server
+        .handle_request(C::ID, session_index)
+        .expect("Should have routed the request");
+    Command::<O>::read()
+        .expect("Should have read the reply")
+        .into_data()
+}
+
+/// Writes a command's output the way it would be written to the command buffer,
+/// so output layouts can be checked byte for byte.
+pub fn write_command_output<T: EndianWrite>(output: &T) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.checked_write_le(0, output);
+    bytes
+}
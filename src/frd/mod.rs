@@ -2,6 +2,8 @@ pub mod context;
 pub mod frda;
 pub mod frdn;
 pub mod frdu;
+#[cfg(all(test, not(target_os = "horizon")))]
+pub mod harness;
 pub mod notification;
 mod online_play;
 mod result;
@@ -1,4 +1,6 @@
+use core::sync::atomic::{AtomicBool, Ordering};
 use ctr::{
+    ac::AcController,
     ptm_sysm,
     ptm_sysm::{
         sys_get_notification_ack_value, sys_notify_sleep_preparation_complete,
@@ -7,6 +9,65 @@ use ctr::{
     sysmodule::notification::NotificationHandlerResult,
 };
 
+/// Set by AllowHalfAwake. This lives here rather than on the context, since notification handlers don't have access to it.
+///
+/// Wifi is left alone during sleep until a client disallows half awake.
+static HALF_AWAKE_ALLOWED: AtomicBool = AtomicBool::new(true);
+
+pub fn set_half_awake_allowed(half_awake_allowed: bool) {
+    HALF_AWAKE_ALLOWED.store(half_awake_allowed, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+static HALF_AWAKE_TEST_LOCK: AtomicBool = AtomicBool::new(false);
+
+/// Tests run in parallel and share [HALF_AWAKE_ALLOWED], so tests that change it hold this.
+/// Dropping it puts half awake back to its default.
+#[cfg(test)]
+pub struct HalfAwakeTestGuard;
+
+#[cfg(test)]
+impl HalfAwakeTestGuard {
+    pub fn lock() -> Self {
+        while HALF_AWAKE_TEST_LOCK
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            core::hint::spin_loop();
+        }
+
+        Self
+    }
+}
+
+#[cfg(test)]
+impl Drop for HalfAwakeTestGuard {
+    fn drop(&mut self) {
+        set_half_awake_allowed(true);
+        HALF_AWAKE_TEST_LOCK.store(false, Ordering::Release);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SleepWiFiAction {
+    KeepConnected,
+    Disconnect,
+}
+
+/// Half awake lets the console keep its friend presence while asleep, which needs wifi.
+pub fn get_sleep_wifi_action(half_awake_allowed: bool) -> SleepWiFiAction {
+    if half_awake_allowed {
+        SleepWiFiAction::KeepConnected
+    } else {
+        SleepWiFiAction::Disconnect
+    }
+}
+
+/// What the sleep handler does with wifi, going by the last AllowHalfAwake.
+pub fn get_current_sleep_wifi_action() -> SleepWiFiAction {
+    get_sleep_wifi_action(HALF_AWAKE_ALLOWED.load(Ordering::Relaxed))
+}
+
 /// The notification Id is currently a u32 to avoid assumptions about the notifications that might be sent.
 ///
 /// However it's probably safe to assume only [0x100, 0x179](https://github.com/LumaTeam/Luma3DS/blob/ebeef7ab7f730ae35658b66ca97c5da9f663a17d/sysmodules/loader/source/service_manager.c#L58-L59), and subscribed notifications will be used here, so an enum may be better here in the future.
@@ -14,6 +75,12 @@ pub fn handle_sleep_notification(notification_id: u32) -> NotificationHandlerRes
     let _session = ptm_sysm::Session::new()?;
 
     if notification_id == ptm_sysm::NotificationId::SleepRequested {
+        if get_current_sleep_wifi_action() == SleepWiFiAction::Disconnect {
+            // We may not be connected, and that shouldn't stop the console from sleeping.
+            // The context's connection status catches up with AC, and NDM is signaled, on the next frd:n request.
+            let _ = AcController::disconnect();
+        }
+
         // Sleeping and logging seem to interfere with each other,
         // so we deny sleeping when in dev mode
         #[cfg(debug_assertions)]
@@ -28,3 +95,44 @@ pub fn handle_sleep_notification(notification_id: u32) -> NotificationHandlerRes
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    mod get_sleep_wifi_action {
+        use super::*;
+
+        #[test]
+        fn should_keep_wifi_connected_when_half_awake_is_allowed() {
+            let result = get_sleep_wifi_action(true);
+            assert_eq!(result, SleepWiFiAction::KeepConnected);
+        }
+
+        #[test]
+        fn should_disconnect_wifi_when_half_awake_is_not_allowed() {
+            let result = get_sleep_wifi_action(false);
+            assert_eq!(result, SleepWiFiAction::Disconnect);
+        }
+    }
+
+    mod get_current_sleep_wifi_action {
+        use super::*;
+
+        #[test]
+        fn should_keep_wifi_connected_by_default() {
+            let _guard = HalfAwakeTestGuard::lock();
+            assert_eq!(
+                get_current_sleep_wifi_action(),
+                SleepWiFiAction::KeepConnected
+            );
+        }
+
+        #[test]
+        fn should_disconnect_wifi_once_half_awake_is_disallowed() {
+            let _guard = HalfAwakeTestGuard::lock();
+            set_half_awake_allowed(false);
+            assert_eq!(get_current_sleep_wifi_action(), SleepWiFiAction::Disconnect);
+        }
+    }
+}
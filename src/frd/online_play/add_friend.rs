@@ -0,0 +1,172 @@
+use super::{
+    base_request::{create_game_server_request, download_nasc_response},
+    http::NascHttpRequest,
+    return_code::NascReturnCode,
+    utils::{parse_num_from_base64, MAX_NASC_RESPONSE_SIZE},
+};
+use crate::frd::{
+    context::FriendServiceContext, result::FrdErrorCode, utils::convert_principal_id_to_friend_code,
+};
+use alloc::format;
+use core::str::FromStr;
+use ctr::{frd::FriendKey, result::CtrResult};
+
+/// There's no known NASC action for adding a friend, so this action and its field are
+/// specific to this sysmodule, for private servers that choose to implement them.
+const ADD_FRIEND_ACTION: &str = "ADDFRIEND";
+const ADD_FRIEND_PRINCIPAL_ID_FIELD: &str = "friendpid";
+
+/// Gets the returncd from a NASC response, which is all an add friend response has.
+fn parse_return_code(response: &str) -> CtrResult<u32> {
+    let field_delimeter = char::from_str("&").unwrap();
+    let value_delimeter = char::from_str("=").unwrap();
+
+    for field in response.split(field_delimeter) {
+        let mut split_field = field.split(value_delimeter);
+
+        if let (Some("returncd"), Some(inner_value)) = (split_field.next(), split_field.next()) {
+            return parse_num_from_base64(inner_value);
+        }
+    }
+
+    Err(FrdErrorCode::InvalidNascResponse.into())
+}
+
+pub fn create_add_friend_request(
+    context: &FriendServiceContext,
+    requesting_process_id: u32,
+    requesting_game_id: u32,
+    sdk_version_low: u8,
+    sdk_version_high: u8,
+    principal_id: u32,
+) -> CtrResult<NascHttpRequest> {
+    let request = create_game_server_request(
+        context,
+        requesting_process_id,
+        requesting_game_id,
+        sdk_version_low,
+        sdk_version_high,
+    )?;
+    request.add_post_base64_field("action", ADD_FRIEND_ACTION)?;
+    request.add_post_base64_field(ADD_FRIEND_PRINCIPAL_ID_FIELD, &format!("{}", principal_id))?;
+
+    Ok(request)
+}
+
+/// Sends an add friend request, and adds the friend locally if the server accepted it.
+pub fn fetch_add_friend(
+    context: &mut FriendServiceContext,
+    session_index: usize,
+    request: &NascHttpRequest,
+    principal_id: u32,
+) -> CtrResult {
+    let mut buffer = [0; MAX_NASC_RESPONSE_SIZE + 1];
+    let nasc_response = download_nasc_response(request, &mut buffer);
+    context.session_contexts[session_index].last_response_result = nasc_response.map(|_| ());
+    let (_, response) = nasc_response?;
+
+    let return_code = parse_return_code(response)?;
+    let response_result = NascReturnCode::from_raw(return_code).into_result();
+    context.session_contexts[session_index].last_response_result = response_result;
+    response_result?;
+
+    context.add_friend(FriendKey {
+        principal_id,
+        padding: 0,
+        local_friend_code: convert_principal_id_to_friend_code(principal_id)?,
+    })?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    mod create_add_friend_request {
+        use super::*;
+
+        #[test]
+        fn should_add_the_friend_fields() {
+            let context = FriendServiceContext::new().unwrap();
+
+            let request =
+                create_add_friend_request(&context, 1, 0x00123400, 11, 2, 0x55667788).unwrap();
+
+            assert_eq!(
+                request.get_post_field("action"),
+                Some(b"ADDFRIEND".to_vec())
+            );
+            assert_eq!(
+                request.get_post_field("friendpid"),
+                Some(b"1432778632".to_vec())
+            );
+            assert_eq!(
+                request.get_post_field("userid"),
+                Some(b"2864434397".to_vec())
+            );
+        }
+    }
+
+    mod fetch_add_friend {
+        use super::*;
+
+        #[test]
+        fn should_add_the_friend_when_the_server_accepts() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.accept_session(0);
+            let request =
+                create_add_friend_request(&context, 1, 0x00123400, 11, 2, 0x55667788).unwrap();
+            // returncd is "001"
+            request.set_response(200, "returncd=MDAx");
+
+            fetch_add_friend(&mut context, 0, &request, 0x55667788).expect("Should have added");
+
+            assert_eq!(context.session_contexts[0].last_response_result, Ok(()));
+            let friend = context
+                .get_friend_by_principal_id(0x55667788)
+                .expect("Should have added the friend");
+            assert_eq!(
+                friend.friend_key.local_friend_code,
+                convert_principal_id_to_friend_code(0x55667788).unwrap()
+            );
+        }
+
+        #[test]
+        fn should_not_add_the_friend_when_the_server_rejects() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.accept_session(0);
+            let request =
+                create_add_friend_request(&context, 1, 0x00123400, 11, 2, 0x55667788).unwrap();
+            // returncd is "102"
+            request.set_response(200, "returncd=MTAy");
+
+            let result = fetch_add_friend(&mut context, 0, &request, 0x55667788);
+
+            assert_eq!(
+                result,
+                Err(FrdErrorCode::NascRequestRejected.into_result_code())
+            );
+            assert!(context.get_friend_by_principal_id(0x55667788).is_none());
+        }
+
+        #[test]
+        fn should_return_an_error_when_the_server_is_unreachable() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.accept_session(0);
+            let request =
+                create_add_friend_request(&context, 1, 0x00123400, 11, 2, 0x55667788).unwrap();
+
+            let result = fetch_add_friend(&mut context, 0, &request, 0x55667788);
+
+            assert_eq!(
+                result,
+                Err(FrdErrorCode::NascRequestFailed.into_result_code())
+            );
+            assert_eq!(
+                context.session_contexts[0].last_response_result,
+                Err(FrdErrorCode::NascRequestFailed)
+            );
+        }
+    }
+}
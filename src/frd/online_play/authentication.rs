@@ -1,18 +1,41 @@
 use super::{
-    base_request::create_game_server_request,
-    utils::{parse_address, parse_datetime_from_base64, parse_num_from_base64},
+    base_request::{create_game_server_request, download_nasc_response},
+    cache::CachedResponse,
+    http::NascHttpRequest,
+    return_code::NascReturnCode,
+    utils::{
+        parse_address, parse_datetime_from_base64, parse_num_from_base64, MAX_NASC_RESPONSE_SIZE,
+    },
 };
-use crate::frd::context::FriendServiceContext;
+use crate::frd::{context::FriendServiceContext, result::FrdErrorCode};
 use alloc::str;
 use core::str::FromStr;
 use ctr::{
-    http::HttpContext,
     result::CtrResult,
     time::SystemTimestamp,
     utils::{base64_decode, copy_into_slice},
 };
 use no_std_io::{EndianRead, EndianWrite};
 
+/// How long, in seconds, a game authentication token can be handed out after it was fetched.
+/// This is shorter than the service locator, since game servers reject old tokens sooner.
+pub const GAME_AUTHENTICATION_TTL: u64 = 10 * 60;
+
+pub const INGAMESN_SIZE: usize = 24;
+
+/// Reads the in-game screen name a game sent with its login request.
+///
+/// The name has to be null terminated within the field and valid UTF-8, so a bad name
+/// is rejected before a request is made rather than sent to the server as garbage.
+pub fn parse_ingamesn(ingamesn_bytes: &[u8; INGAMESN_SIZE]) -> Result<&str, FrdErrorCode> {
+    let ingamesn_len = ingamesn_bytes
+        .iter()
+        .position(|byte| *byte == 0)
+        .ok_or(FrdErrorCode::InvalidArguments)?;
+
+    str::from_utf8(&ingamesn_bytes[..ingamesn_len]).map_err(|_| FrdErrorCode::InvalidArguments)
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy, EndianRead, EndianWrite)]
 #[repr(C)]
 pub struct GameAuthenticationData {
@@ -67,6 +90,15 @@ impl GameAuthenticationData {
 
         Ok(game_auth_data)
     }
+
+    /// The result clients get from GetLastResponseResult.
+    /// A failed login with the retry flag set can be retried, even without a 3xx return code.
+    pub fn get_result(&self) -> Result<(), FrdErrorCode> {
+        match NascReturnCode::from_raw(self.return_code).into_result() {
+            Err(_) if self.retry != 0 => Err(FrdErrorCode::NascRetryLater),
+            result => result,
+        }
+    }
 }
 
 impl Default for GameAuthenticationData {
@@ -90,7 +122,7 @@ pub fn create_game_login_request(
     sdk_version_low: u8,
     sdk_version_high: u8,
     ingamesn: &str,
-) -> CtrResult<HttpContext> {
+) -> CtrResult<NascHttpRequest> {
     let request = create_game_server_request(
         context,
         requesting_process_id,
@@ -104,10 +136,35 @@ pub fn create_game_login_request(
     Ok(request)
 }
 
+/// Sends a login request and stores the response for the session.
+pub fn fetch_game_authentication(
+    context: &mut FriendServiceContext,
+    session_index: usize,
+    request: &NascHttpRequest,
+) -> CtrResult {
+    let mut buffer = [0; MAX_NASC_RESPONSE_SIZE + 1];
+    let nasc_response = download_nasc_response(request, &mut buffer);
+    context.session_contexts[session_index].last_response_result = nasc_response.map(|_| ());
+    let (response_status_code, response) = nasc_response?;
+
+    let authentication_response =
+        GameAuthenticationData::from_fetched_response(response, response_status_code)?;
+
+    // The response is kept either way, since clients read the return code themselves
+    let session_context = &mut context.session_contexts[session_index];
+    session_context.last_response_result = authentication_response.get_result();
+    session_context.last_game_authentication_response =
+        Some(CachedResponse::new(authentication_response));
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    const AUTH_RESPONSE: &str = "locator=MTI3LjAuMC4xOjcwMDA*&retry=MA**&returncd=MDAx&token=AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8gISIjJCUmJygpKissLS4vMDE*&datetime=MjAyMTAxMDIwMzA0MDU*";
+
     mod game_authentication_data {
         use super::*;
         use alloc::vec;
@@ -181,4 +238,148 @@ mod test {
             assert_eq!(game_auth_bytes, [0; 312])
         }
     }
+
+    mod parse_ingamesn {
+        use super::*;
+
+        fn create_ingamesn_bytes(ingamesn: &[u8]) -> [u8; INGAMESN_SIZE] {
+            let mut bytes = [0; INGAMESN_SIZE];
+            bytes[..ingamesn.len()].copy_from_slice(ingamesn);
+            bytes
+        }
+
+        #[test]
+        fn should_parse_a_terminated_name() {
+            let bytes = create_ingamesn_bytes(b"Player");
+            let result = parse_ingamesn(&bytes);
+            assert_eq!(result, Ok("Player"));
+        }
+
+        #[test]
+        fn should_accept_an_empty_name() {
+            let bytes = create_ingamesn_bytes(b"");
+            let result = parse_ingamesn(&bytes);
+            assert_eq!(result, Ok(""));
+        }
+
+        #[test]
+        fn should_reject_a_name_without_a_terminator() {
+            let bytes = [b'a'; INGAMESN_SIZE];
+            let result = parse_ingamesn(&bytes);
+            assert_eq!(result, Err(FrdErrorCode::InvalidArguments));
+        }
+
+        #[test]
+        fn should_reject_a_name_that_is_not_utf8() {
+            let bytes = create_ingamesn_bytes(&[0xff, 0xfe]);
+            let result = parse_ingamesn(&bytes);
+            assert_eq!(result, Err(FrdErrorCode::InvalidArguments));
+        }
+    }
+
+    mod fetch_game_authentication {
+        use super::*;
+
+        #[test]
+        fn should_build_the_login_request() {
+            let context = FriendServiceContext::new().unwrap();
+            let request =
+                create_game_login_request(&context, 1, 0x00123400, 11, 2, "Player").unwrap();
+
+            assert_eq!(request.get_header("X-GameId"), Some("00123400".into()));
+            assert_eq!(request.get_post_field("action"), Some(b"LOGIN".to_vec()));
+            assert_eq!(request.get_post_field("ingamesn"), Some(b"Player".to_vec()));
+            assert_eq!(request.get_post_field("sdkver"), Some(b"011002".to_vec()));
+            assert_eq!(
+                request.get_post_field("userid"),
+                Some(b"2864434397".to_vec())
+            );
+            assert_eq!(
+                request.get_post_field("devtime"),
+                Some(b"210102030405".to_vec())
+            );
+            assert_eq!(request.get_post_field("romid"), None);
+        }
+
+        #[test]
+        fn should_store_the_response_in_the_session() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.accept_session(0);
+
+            let request =
+                create_game_login_request(&context, 1, 0x00123400, 11, 2, "Player").unwrap();
+            request.set_response(200, AUTH_RESPONSE);
+
+            fetch_game_authentication(&mut context, 0, &request).expect("Should have fetched");
+
+            let session_context = &context.session_contexts[0];
+            assert_eq!(session_context.last_response_result, Ok(()));
+            assert_eq!(
+                session_context
+                    .last_game_authentication_response
+                    .map(|response| response.data),
+                Some(GameAuthenticationData::from_fetched_response(AUTH_RESPONSE, 200).unwrap())
+            );
+        }
+
+        #[test]
+        fn should_record_a_rejected_login() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.accept_session(0);
+
+            let request =
+                create_game_login_request(&context, 1, 0x00123400, 11, 2, "Player").unwrap();
+            request.set_response(200, "retry=MA**&returncd=MTAy");
+
+            fetch_game_authentication(&mut context, 0, &request).expect("Should have fetched");
+
+            let session_context = &context.session_contexts[0];
+            assert_eq!(
+                session_context.last_response_result,
+                Err(FrdErrorCode::NascRequestRejected)
+            );
+            assert!(session_context.last_game_authentication_response.is_some());
+        }
+
+        #[test]
+        fn should_record_a_failed_request() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.accept_session(0);
+
+            let request =
+                create_game_login_request(&context, 1, 0x00123400, 11, 2, "Player").unwrap();
+
+            let result = fetch_game_authentication(&mut context, 0, &request);
+
+            assert!(result.is_err());
+            let session_context = &context.session_contexts[0];
+            assert_eq!(
+                session_context.last_response_result,
+                Err(FrdErrorCode::NascRequestFailed)
+            );
+            assert_eq!(session_context.last_game_authentication_response, None);
+        }
+    }
+
+    mod get_result {
+        use super::*;
+
+        #[test]
+        fn should_be_retryable_when_the_retry_flag_is_set() {
+            // retry is 1 and returncd is "102"
+            let auth_data =
+                GameAuthenticationData::from_fetched_response("retry=MQ**&returncd=MTAy", 200)
+                    .unwrap();
+            assert_eq!(auth_data.get_result(), Err(FrdErrorCode::NascRetryLater));
+        }
+
+        #[test]
+        fn should_succeed_even_with_the_retry_flag_set() {
+            // retry is 1 and returncd is "001"
+            let auth_data =
+                GameAuthenticationData::from_fetched_response("retry=MQ**&returncd=MDAx", 200)
+                    .unwrap();
+            assert_eq!(auth_data.get_result(), Ok(()));
+        }
+    }
 }
@@ -1,17 +1,27 @@
-use crate::frd::context::FriendServiceContext;
-use alloc::{format, str, vec::Vec};
-use ctr::{
-    ac::{acu_get_current_ap_info, acu_get_wifi_status},
-    cfg::{get_console_username, get_local_friend_code_seed_data},
-    fs,
-    fs::MediaType,
-    http::{DefaultRootCert, HttpContext, RequestMethod},
-    os::get_time,
-    ps::get_rom_id,
-    result::CtrResult,
-    time::SystemTimestamp,
-    utils::cstring::parse_null_terminated_str,
+use super::{
+    http::{create_nasc_http_request, get_nasc_device_info, NascDeviceInfo, NascHttpRequest},
+    utils::{normalize_mac_address, parse_nasc_response, NascResponseBuffer},
 };
+use crate::frd::{context::FriendServiceContext, result::FrdErrorCode};
+use alloc::format;
+use ctr::result::CtrResult;
+
+const NASC_URL: &str = "https://nasc.nintendowifi.net/ac";
+
+/// Console usernames are up to 10 UTF-16 characters, and are sent as raw UTF-16 bytes.
+const MAX_DEVICE_NAME_SIZE: usize = 20;
+
+/// The mydata serial number field holds up to 15 characters plus a null terminator.
+const MAX_CONSOLE_SERIAL_NUMBER_SIZE: usize = 15;
+
+/// NASC rejects an overlong field without saying which one, so they're checked before sending.
+fn validate_nasc_field_size(value: &[u8], max_size: usize) -> Result<(), FrdErrorCode> {
+    if value.len() > max_size {
+        return Err(FrdErrorCode::InvalidArguments);
+    }
+
+    Ok(())
+}
 
 pub fn create_game_server_request(
     context: &FriendServiceContext,
@@ -19,14 +29,31 @@ pub fn create_game_server_request(
     requesting_game_id: u32,
     sdk_version_low: u8,
     sdk_version_high: u8,
-) -> CtrResult<HttpContext> {
-    let url = "https://nasc.nintendowifi.net/ac";
-    let request = HttpContext::new(url, RequestMethod::Post)?;
+) -> CtrResult<NascHttpRequest> {
+    let device_info = get_nasc_device_info(requesting_process_id)?;
+    create_game_server_request_for_device(
+        context,
+        &device_info,
+        requesting_game_id,
+        sdk_version_low,
+        sdk_version_high,
+    )
+}
+
+pub fn create_game_server_request_for_device(
+    context: &FriendServiceContext,
+    device_info: &NascDeviceInfo,
+    requesting_game_id: u32,
+    sdk_version_low: u8,
+    sdk_version_high: u8,
+) -> CtrResult<NascHttpRequest> {
+    validate_nasc_field_size(&device_info.device_name, MAX_DEVICE_NAME_SIZE)?;
+    validate_nasc_field_size(
+        context.my_data.console_serial_number.as_bytes(),
+        MAX_CONSOLE_SERIAL_NUMBER_SIZE,
+    )?;
 
-    request.add_default_cert(DefaultRootCert::NintendoCa)?;
-    request.add_default_cert(DefaultRootCert::NintendoCaG2)?;
-    request.add_default_cert(DefaultRootCert::NintendoCaG3)?;
-    request.set_client_cert_default()?;
+    let request = create_nasc_http_request(NASC_URL)?;
 
     request.add_header("X-GameId", &format!("{:08X}", requesting_game_id))?;
     // The official sysmodule effectively does `format!("CTR FPD/{:04X}", get_value())`,
@@ -40,59 +67,41 @@ pub fn create_game_server_request(
     // but this should be removed once official servers are down.
     request.add_header("Content-Type", "application/x-www-form-urlencoded")?;
 
-    let program_info = fs::user::get_program_launch_info(requesting_process_id)?;
-    let product_info = fs::user::get_product_info(requesting_process_id)?;
-
     request.add_post_base64_field("gameid", &format!("{:08X}", requesting_game_id))?;
     request.add_post_base64_field(
         "sdkver",
         &format!("{:03}{:03}", sdk_version_low, sdk_version_high),
     )?;
-    request.add_post_base64_field("titleid", &format!("{:016X}", program_info.program_id))?;
-    // The friends list app always uses "----", but it's the only thing
-    // Since the friends online play is not being added, we don't have to worry about it
-    let product_code = parse_null_terminated_str(&product_info.product_code[6..10]);
-    request.add_post_base64_field("gamecd", product_code)?;
-    request.add_post_base64_field("gamever", &format!("{:04X}", product_info.remaster_version))?;
-    request.add_post_base64_field("mediatype", &format!("{}", program_info.media_type as u8))?;
-
-    if program_info.media_type == MediaType::GameCard {
-        let rom_id = get_rom_id(requesting_process_id)?;
-        request.add_post_base64_field("romid", rom_id.get_inner())?;
+    request.add_post_base64_field("titleid", &format!("{:016X}", device_info.title_id))?;
+    request.add_post_base64_field("gamecd", &device_info.game_code)?;
+    request.add_post_base64_field("gamever", &device_info.game_version)?;
+    request.add_post_base64_field("mediatype", &format!("{}", device_info.media_type))?;
+
+    if let Some(rom_id) = &device_info.rom_id {
+        request.add_post_base64_field("romid", rom_id)?;
     }
 
-    let company_code = str::from_utf8(&product_info.company_code)?;
-    request.add_post_base64_field("makercd", company_code)?;
+    request.add_post_base64_field("makercd", &device_info.maker_code)?;
     request.add_post_base64_field("unitcd", "2")?;
-    request.add_post_base64_field("macadr", &context.my_data.mac_address)?;
-
-    let ap_info = acu_get_current_ap_info()?;
-    request.add_post_base64_field("bssid", &ap_info.get_formatted_bssid())?;
-
-    // This normally uses ACU_GetWifiStatus, ACU_GetNZoneApNumService, and ACU_GetConnectingHotspotSubset,
-    // but NZone is down and most people should always have the same data here, so we'll skip the extra logic for now.
-    let wifi_status = acu_get_wifi_status()?;
-    request.add_post_base64_field("apinfo", &format!("{:02}:0000000000", wifi_status))?;
-
-    let local_friend_code_seed = get_local_friend_code_seed_data()?;
-    request.add_post_base64_field("fcdcert", local_friend_code_seed)?;
-
-    let console_username = get_console_username()?
-        .encode_utf16()
-        .flat_map(|short| short.to_le_bytes())
-        .collect::<Vec<u8>>();
-    request.add_post_base64_field("devname", &console_username)?;
+    request.add_post_base64_field(
+        "macadr",
+        normalize_mac_address(&context.my_data.mac_address)?,
+    )?;
+    request.add_post_base64_field("bssid", &device_info.bssid)?;
+    request.add_post_base64_field("apinfo", &device_info.ap_info)?;
+    request.add_post_base64_field("fcdcert", &device_info.friend_code_seed)?;
+    request.add_post_base64_field("devname", &device_info.device_name)?;
 
     // Has special formatting
     request.add_post_base64_field(
         "servertype",
-        context.account_config.get_server_type_string(),
+        context.account_config.get_server_type_string()?,
     )?;
 
     // This looks to be hardcoded to '000F', but I'm curious if that's the case for all models/fw versions
     request.add_post_base64_field("fpdver", "000F")?;
 
-    let current_time = SystemTimestamp::new(get_time());
+    let current_time = device_info.device_time;
     let current_year_month_date = current_time.get_year_month_date();
     request.add_post_base64_field(
         "devtime",
@@ -123,3 +132,149 @@ pub fn create_game_server_request(
 
     Ok(request)
 }
+
+/// Downloads a NASC response, returning the http status code and the response body.
+pub fn download_nasc_response<'a>(
+    request: &NascHttpRequest,
+    buffer: &'a mut NascResponseBuffer,
+) -> Result<(u32, &'a str), FrdErrorCode> {
+    request
+        .download_data_into_buffer(buffer)
+        .map_err(|_| FrdErrorCode::NascRequestFailed)?;
+
+    let response_status_code = request
+        .get_response_status_code()
+        .map_err(|_| FrdErrorCode::NascRequestFailed)?;
+
+    let response = parse_nasc_response(buffer)?;
+    Ok((response_status_code, response))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frd::online_play::http::{
+        get_ap_info_or_default, get_bssid_or_default, DEFAULT_AP_INFO, DEFAULT_BSSID,
+    };
+    use crate::frd::online_play::utils::MAX_NASC_RESPONSE_SIZE;
+    use alloc::string::String;
+    use ctr::result::error;
+
+    mod create_game_server_request {
+        use super::*;
+        use crate::frd::online_play::http::MOCK_EXITED_PROCESS_ID;
+
+        #[test]
+        fn should_return_an_error_when_the_requesting_process_is_gone() {
+            let context = FriendServiceContext::new().unwrap();
+
+            let result =
+                create_game_server_request(&context, MOCK_EXITED_PROCESS_ID, 0x00123400, 1, 2);
+
+            assert_eq!(
+                result.err(),
+                Some(FrdErrorCode::RequestingProcessGone.into_result_code())
+            );
+        }
+    }
+
+    mod create_game_server_request_for_device {
+        use super::*;
+
+        #[test]
+        fn should_build_the_request_when_the_ap_info_is_missing() {
+            let context = FriendServiceContext::new().unwrap();
+            let mut device_info = get_nasc_device_info(0).unwrap();
+            device_info.bssid = get_bssid_or_default(Err(error::invalid_value()));
+            device_info.ap_info = get_ap_info_or_default(Err(error::invalid_value()));
+
+            let request =
+                create_game_server_request_for_device(&context, &device_info, 0x00123400, 1, 2)
+                    .expect("Should have built the request");
+
+            assert_eq!(
+                request.get_post_field("bssid"),
+                Some(DEFAULT_BSSID.as_bytes().to_vec())
+            );
+            assert_eq!(
+                request.get_post_field("apinfo"),
+                Some(DEFAULT_AP_INFO.as_bytes().to_vec())
+            );
+            assert_eq!(
+                request.get_post_field("userid"),
+                Some(b"2864434397".to_vec())
+            );
+        }
+
+        #[test]
+        fn should_return_an_error_for_an_overlong_device_name() {
+            let context = FriendServiceContext::new().unwrap();
+            let mut device_info = get_nasc_device_info(0).unwrap();
+            device_info.device_name = [0x4d, 0x00].repeat(11);
+
+            let result =
+                create_game_server_request_for_device(&context, &device_info, 0x00123400, 1, 2)
+                    .err();
+
+            assert_eq!(
+                result,
+                Some(FrdErrorCode::InvalidArguments.into_result_code())
+            );
+        }
+
+        #[test]
+        fn should_return_an_error_for_an_overlong_console_serial_number() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.my_data.console_serial_number = "CW1234567890123456".into();
+            let device_info = get_nasc_device_info(0).unwrap();
+
+            let result =
+                create_game_server_request_for_device(&context, &device_info, 0x00123400, 1, 2)
+                    .err();
+
+            assert_eq!(
+                result,
+                Some(FrdErrorCode::InvalidArguments.into_result_code())
+            );
+        }
+    }
+
+    mod validate_nasc_field_size {
+        use super::*;
+
+        #[test]
+        fn should_accept_a_field_at_the_max_size() {
+            let result = validate_nasc_field_size(&[0; 20], MAX_DEVICE_NAME_SIZE);
+            assert_eq!(result, Ok(()));
+        }
+    }
+
+    mod download_nasc_response {
+        use super::*;
+
+        #[test]
+        fn should_return_the_status_code_and_response() {
+            let request = create_nasc_http_request("https://nasc.example").unwrap();
+            request.set_response(200, "returncd=MDAx\r\n");
+            let mut buffer = [0; MAX_NASC_RESPONSE_SIZE + 1];
+
+            let result = download_nasc_response(&request, &mut buffer);
+
+            assert_eq!(result, Ok((200, "returncd=MDAx")));
+        }
+
+        #[test]
+        fn should_error_instead_of_truncating_an_oversized_response() {
+            let request = create_nasc_http_request("https://nasc.example").unwrap();
+            let response: String = core::iter::repeat('a')
+                .take(MAX_NASC_RESPONSE_SIZE + 10)
+                .collect();
+            request.set_response(200, &response);
+            let mut buffer = [0; MAX_NASC_RESPONSE_SIZE + 1];
+
+            let result = download_nasc_response(&request, &mut buffer);
+
+            assert_eq!(result, Err(FrdErrorCode::NascResponseTooLarge));
+        }
+    }
+}
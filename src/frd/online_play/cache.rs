@@ -0,0 +1,87 @@
+use super::utils::get_current_unix_timestamp;
+
+/// A NASC response along with when it was fetched, so stale tokens aren't handed out.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct CachedResponse<T: Copy> {
+    pub data: T,
+    pub fetched_at: u64,
+}
+
+impl<T: Copy> CachedResponse<T> {
+    pub fn new(data: T) -> Self {
+        Self {
+            data,
+            fetched_at: get_current_unix_timestamp(),
+        }
+    }
+
+    /// Returns the data if it's no older than `ttl` seconds.
+    pub fn get_if_fresh(&self, ttl: u64) -> Option<T> {
+        self.get_if_fresh_at(get_current_unix_timestamp(), ttl)
+    }
+
+    /// Returns the data if it's no older than `ttl` seconds at the unix timestamp `now`.
+    /// A `now` before the fetch, such as after the clock was set back, counts as fresh.
+    pub fn get_if_fresh_at(&self, now: u64, ttl: u64) -> Option<T> {
+        let elapsed = now.saturating_sub(self.fetched_at);
+
+        if elapsed > ttl {
+            None
+        } else {
+            Some(self.data)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    mod get_if_fresh {
+        use super::*;
+
+        #[test]
+        fn should_return_data_that_was_just_fetched() {
+            let cached_response = CachedResponse::new(5u32);
+            assert_eq!(cached_response.get_if_fresh(60), Some(5));
+        }
+
+        #[test]
+        fn should_return_none_for_data_fetched_longer_ago_than_the_ttl() {
+            let cached_response = CachedResponse {
+                data: 5u32,
+                fetched_at: get_current_unix_timestamp() - 61,
+            };
+            assert_eq!(cached_response.get_if_fresh(60), None);
+        }
+    }
+
+    mod get_if_fresh_at {
+        use super::*;
+
+        const CACHED_RESPONSE: CachedResponse<u32> = CachedResponse {
+            data: 5,
+            fetched_at: 100,
+        };
+
+        #[test]
+        fn should_return_fresh_data() {
+            assert_eq!(CACHED_RESPONSE.get_if_fresh_at(110, 60), Some(5));
+        }
+
+        #[test]
+        fn should_return_data_at_exactly_the_ttl() {
+            assert_eq!(CACHED_RESPONSE.get_if_fresh_at(160, 60), Some(5));
+        }
+
+        #[test]
+        fn should_return_none_for_expired_data() {
+            assert_eq!(CACHED_RESPONSE.get_if_fresh_at(161, 60), None);
+        }
+
+        #[test]
+        fn should_return_data_when_now_is_before_the_fetch() {
+            assert_eq!(CACHED_RESPONSE.get_if_fresh_at(50, 60), Some(5));
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use super::{get_ap_info_or_default, get_bssid_or_default, NascDeviceInfo};
+use crate::frd::result::FrdErrorCode;
+use alloc::{format, str, string::ToString, vec::Vec};
+use ctr::{
+    ac::{acu_get_current_ap_info, acu_get_wifi_status},
+    cfg::{get_console_username, get_local_friend_code_seed_data},
+    fs,
+    fs::MediaType,
+    http::{DefaultRootCert, HttpContext, RequestMethod},
+    os::get_time,
+    ps::get_rom_id,
+    result::CtrResult,
+    svc,
+    time::SystemTimestamp,
+    utils::cstring::parse_null_terminated_str,
+};
+
+pub type NascHttpRequest = HttpContext;
+
+pub fn create_nasc_http_request(url: &str) -> CtrResult<NascHttpRequest> {
+    let request = HttpContext::new(url, RequestMethod::Post)?;
+
+    request.add_default_cert(DefaultRootCert::NintendoCa)?;
+    request.add_default_cert(DefaultRootCert::NintendoCaG2)?;
+    request.add_default_cert(DefaultRootCert::NintendoCaG3)?;
+    request.set_client_cert_default()?;
+
+    Ok(request)
+}
+
+pub fn get_nasc_device_info(requesting_process_id: u32) -> CtrResult<NascDeviceInfo> {
+    // The requesting game can exit before its request gets here, which makes these lookups fail
+    let program_info = fs::user::get_program_launch_info(requesting_process_id)
+        .map_err(|_| FrdErrorCode::RequestingProcessGone)?;
+    let product_info = fs::user::get_product_info(requesting_process_id)
+        .map_err(|_| FrdErrorCode::RequestingProcessGone)?;
+
+    let rom_id = if program_info.media_type == MediaType::GameCard {
+        Some(get_rom_id(requesting_process_id)?.get_inner().to_vec())
+    } else {
+        None
+    };
+
+    // The friends list app always uses "----", but it's the only thing
+    // Since the friends online play is not being added, we don't have to worry about it
+    let game_code = parse_null_terminated_str(&product_info.product_code[6..10]).to_string();
+
+    // This normally uses ACU_GetWifiStatus, ACU_GetNZoneApNumService, and ACU_GetConnectingHotspotSubset,
+    // but NZone is down and most people should always have the same data here, so we'll skip the extra logic for now.
+    let ap_info = get_ap_info_or_default(acu_get_wifi_status());
+    let bssid = get_bssid_or_default(
+        acu_get_current_ap_info().map(|current_ap_info| current_ap_info.get_formatted_bssid()),
+    );
+
+    let device_name = get_console_username()?
+        .encode_utf16()
+        .flat_map(|short| short.to_le_bytes())
+        .collect::<Vec<u8>>();
+
+    Ok(NascDeviceInfo {
+        title_id: program_info.program_id,
+        game_code,
+        game_version: format!("{:04X}", product_info.remaster_version),
+        media_type: program_info.media_type as u8,
+        rom_id,
+        maker_code: str::from_utf8(&product_info.company_code)?.to_string(),
+        bssid,
+        ap_info,
+        friend_code_seed: get_local_friend_code_seed_data()?.to_vec(),
+        device_name,
+        device_time: SystemTimestamp::new(get_time()),
+    })
+}
+
+pub fn sleep_before_retry(backoff_ns: i64) {
+    svc::sleep_thread(backoff_ns);
+}
@@ -0,0 +1,116 @@
+use super::NascDeviceInfo;
+use crate::frd::result::FrdErrorCode;
+use alloc::{
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::cell::RefCell;
+use ctr::{
+    result::{error, CtrResult},
+    time::FormattedTimestamp,
+};
+
+struct MockResponse {
+    status_code: u32,
+    body: Vec<u8>,
+}
+
+/// A request that records what would be sent and replies with an injected response,
+/// so the NASC request path can be tested on the host.
+pub struct NascHttpRequest {
+    pub url: String,
+    headers: RefCell<Vec<(String, String)>>,
+    post_fields: RefCell<Vec<(String, Vec<u8>)>>,
+    response: RefCell<Option<MockResponse>>,
+}
+
+impl NascHttpRequest {
+    pub fn add_header(&self, name: &str, value: &str) -> CtrResult {
+        self.headers
+            .borrow_mut()
+            .push((name.to_string(), value.to_string()));
+        Ok(())
+    }
+
+    pub fn add_post_base64_field<T: AsRef<[u8]>>(&self, name: &str, value: T) -> CtrResult {
+        self.post_fields
+            .borrow_mut()
+            .push((name.to_string(), value.as_ref().to_vec()));
+        Ok(())
+    }
+
+    pub fn download_data_into_buffer(&self, buffer: &mut [u8]) -> CtrResult {
+        let response = self.response.borrow();
+        let response = response.as_ref().ok_or_else(error::invalid_value)?;
+        let copy_len = response.body.len().min(buffer.len());
+        buffer[..copy_len].copy_from_slice(&response.body[..copy_len]);
+        Ok(())
+    }
+
+    pub fn get_response_status_code(&self) -> CtrResult<u32> {
+        let response = self.response.borrow();
+        let response = response.as_ref().ok_or_else(error::invalid_value)?;
+        Ok(response.status_code)
+    }
+
+    /// Sets the response the request will receive.
+    pub fn set_response(&self, status_code: u32, body: &str) {
+        *self.response.borrow_mut() = Some(MockResponse {
+            status_code,
+            body: body.as_bytes().to_vec(),
+        });
+    }
+
+    pub fn get_header(&self, name: &str) -> Option<String> {
+        self.headers
+            .borrow()
+            .iter()
+            .find(|(header_name, _)| header_name == name)
+            .map(|(_, value)| value.clone())
+    }
+
+    /// Returns a post field before it's base64 encoded.
+    pub fn get_post_field(&self, name: &str) -> Option<Vec<u8>> {
+        self.post_fields
+            .borrow()
+            .iter()
+            .find(|(field_name, _)| field_name == name)
+            .map(|(_, value)| value.clone())
+    }
+}
+
+pub fn create_nasc_http_request(url: &str) -> CtrResult<NascHttpRequest> {
+    Ok(NascHttpRequest {
+        url: url.to_string(),
+        headers: RefCell::new(vec![]),
+        post_fields: RefCell::new(vec![]),
+        response: RefCell::new(None),
+    })
+}
+
+/// A process id the mock treats as having exited, so its program info can't be looked up.
+pub const MOCK_EXITED_PROCESS_ID: u32 = 0xdead;
+
+pub fn get_nasc_device_info(requesting_process_id: u32) -> CtrResult<NascDeviceInfo> {
+    if requesting_process_id == MOCK_EXITED_PROCESS_ID {
+        return Err(FrdErrorCode::RequestingProcessGone.into());
+    }
+
+    Ok(NascDeviceInfo {
+        title_id: 0x0004000000123400,
+        game_code: "ABCE".to_string(),
+        game_version: "0000".to_string(),
+        media_type: 1,
+        rom_id: None,
+        maker_code: "00".to_string(),
+        bssid: "0123456789ab".to_string(),
+        ap_info: "01:0000000000".to_string(),
+        friend_code_seed: vec![0; 0x110],
+        device_name: vec![0x4d, 0x00, 0x6f, 0x00, 0x63, 0x00, 0x6b, 0x00],
+        device_time: FormattedTimestamp::new(2021, 1, 2, 3, 4, 5).into(),
+    })
+}
+
+/// Tests shouldn't wait, so retries happen right away.
+pub fn sleep_before_retry(_backoff_ns: i64) {}
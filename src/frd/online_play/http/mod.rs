@@ -0,0 +1,93 @@
+#[cfg(target_os = "horizon")]
+mod horizon;
+#[cfg(target_os = "horizon")]
+pub use horizon::*;
+
+#[cfg(not(target_os = "horizon"))]
+pub mod mock;
+#[cfg(not(target_os = "horizon"))]
+pub use mock::*;
+
+use crate::log;
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use ctr::{result::CtrResult, time::SystemTimestamp};
+
+pub const DEFAULT_BSSID: &str = "000000000000";
+pub const DEFAULT_AP_INFO: &str = "00:0000000000";
+
+/// Information about the console and requesting process sent with every NASC request.
+pub struct NascDeviceInfo {
+    pub title_id: u64,
+    pub game_code: String,
+    pub game_version: String,
+    pub media_type: u8,
+    /// Only present for game cards.
+    pub rom_id: Option<Vec<u8>>,
+    pub maker_code: String,
+    pub bssid: String,
+    pub ap_info: String,
+    pub friend_code_seed: Vec<u8>,
+    pub device_name: Vec<u8>,
+    pub device_time: SystemTimestamp,
+}
+
+/// The BSSID is cosmetic for most servers, so it falls back to zeros rather than failing the request.
+pub fn get_bssid_or_default(bssid: CtrResult<String>) -> String {
+    bssid.unwrap_or_else(|_| {
+        log::debug("Couldn't get the AP info, using a zeroed bssid");
+        DEFAULT_BSSID.to_string()
+    })
+}
+
+/// Like the BSSID, the AP info falls back to a default rather than failing the request.
+pub fn get_ap_info_or_default(wifi_status: CtrResult<u32>) -> String {
+    match wifi_status {
+        Ok(wifi_status) => format!("{:02}:0000000000", wifi_status),
+        Err(_) => {
+            log::debug("Couldn't get the wifi status, using the default apinfo");
+            DEFAULT_AP_INFO.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ctr::result::error;
+
+    mod get_bssid_or_default {
+        use super::*;
+
+        #[test]
+        fn should_return_the_bssid() {
+            let result = get_bssid_or_default(Ok("0123456789ab".to_string()));
+            assert_eq!(result, "0123456789ab");
+        }
+
+        #[test]
+        fn should_return_a_zeroed_bssid_on_error() {
+            let result = get_bssid_or_default(Err(error::invalid_value()));
+            assert_eq!(result, DEFAULT_BSSID);
+        }
+    }
+
+    mod get_ap_info_or_default {
+        use super::*;
+
+        #[test]
+        fn should_format_the_wifi_status() {
+            let result = get_ap_info_or_default(Ok(1));
+            assert_eq!(result, "01:0000000000");
+        }
+
+        #[test]
+        fn should_return_the_default_on_error() {
+            let result = get_ap_info_or_default(Err(error::invalid_value()));
+            assert_eq!(result, DEFAULT_AP_INFO);
+        }
+    }
+}
@@ -1,17 +1,29 @@
 use super::{
-    base_request::create_game_server_request,
-    utils::{parse_datetime_from_base64, parse_num_from_base64},
+    base_request::{create_game_server_request, download_nasc_response},
+    cache::CachedResponse,
+    http::NascHttpRequest,
+    return_code::NascReturnCode,
+    utils::{
+        calculate_server_time_interval, get_current_unix_timestamp, parse_address,
+        parse_datetime_from_base64, parse_num_from_base64, MAX_NASC_RESPONSE_SIZE,
+    },
 };
-use crate::frd::context::FriendServiceContext;
-use core::{str, str::FromStr};
+use crate::{
+    frd::{context::FriendServiceContext, utils::format_timestamp_for_log},
+    log,
+};
+use alloc::format;
+use core::str::FromStr;
 use ctr::{
-    http::HttpContext,
     result::CtrResult,
     time::SystemTimestamp,
-    utils::{base64_decode, copy_into_slice},
+    utils::{base64_decode, copy_into_slice, cstring::parse_null_terminated_str},
 };
 use no_std_io::{EndianRead, EndianWrite};
 
+/// How long, in seconds, a service locator response can be handed out after it was fetched.
+pub const SERVICE_LOCATOR_TTL: u64 = 60 * 60;
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy, EndianRead, EndianWrite)]
 #[repr(C)]
 pub struct ServiceLocateData {
@@ -62,6 +74,14 @@ impl ServiceLocateData {
 
         Ok(service_locate_data)
     }
+
+    /// Parses the port from `svc_host` when it has one, otherwise returns 0.
+    /// svc_host is kept as is, so a host without a valid port isn't an error.
+    pub fn parse_port(&self) -> u32 {
+        parse_address(parse_null_terminated_str(&self.svc_host))
+            .map(|(_, port)| port)
+            .unwrap_or_default()
+    }
 }
 
 impl Default for ServiceLocateData {
@@ -85,7 +105,7 @@ pub fn create_game_service_locate_request(
     sdk_version_high: u8,
     key_hash: &str,
     svc: &str,
-) -> CtrResult<HttpContext> {
+) -> CtrResult<NascHttpRequest> {
     let request = create_game_server_request(
         context,
         requesting_process_id,
@@ -100,6 +120,40 @@ pub fn create_game_service_locate_request(
     Ok(request)
 }
 
+/// Sends a service locate request and stores the response for the session.
+pub fn fetch_service_locator(
+    context: &mut FriendServiceContext,
+    session_index: usize,
+    request: &NascHttpRequest,
+) -> CtrResult {
+    let mut buffer = [0; MAX_NASC_RESPONSE_SIZE + 1];
+    let nasc_response = download_nasc_response(request, &mut buffer);
+    context.session_contexts[session_index].last_response_result = nasc_response.map(|_| ());
+    let (response_status_code, response) = nasc_response?;
+
+    let service_locator_response =
+        ServiceLocateData::from_fetched_response(response, response_status_code)?;
+
+    // The response is kept either way, since clients read the return code themselves
+    let session_context = &mut context.session_contexts[session_index];
+    session_context.last_response_result =
+        NascReturnCode::from_raw(service_locator_response.return_code).into_result();
+    session_context.last_service_locator_response =
+        Some(CachedResponse::new(service_locator_response));
+
+    let service_locator_timestamp = service_locator_response.timestamp.get_unix_timestamp();
+    session_context.server_time_interval =
+        calculate_server_time_interval(service_locator_timestamp, get_current_unix_timestamp());
+
+    log::debug(&format!(
+        "server time: {}, skew: {}s",
+        format_timestamp_for_log(service_locator_response.timestamp),
+        session_context.server_time_interval
+    ));
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -176,7 +230,7 @@ mod test {
                     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
                     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
                     0x00, 0x00, 0x59, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x88, 0xa8, 0x3d,
-                    0x56, 0x9a, 0x00, 0x00, 0x00
+                    0x56, 0x9a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
                 ]
             )
         }
@@ -190,5 +244,30 @@ mod test {
             let expected_result: [u8; 408] = [0; 408];
             assert_eq!(game_auth_bytes, expected_result)
         }
+
+        #[test]
+        fn should_parse_the_port_from_the_svc_host() {
+            // svchost is "example.com:443"
+            let fetched_response =
+                "returncd=MDA3&svchost=ZXhhbXBsZS5jb206NDQz&datetime=MjAyMTAxMDIwMzA0MDU*";
+            let parsed_response = ServiceLocateData::from_fetched_response(fetched_response, 200)
+                .expect("Should have parsed the response");
+
+            let svc_host_bytes = "example.com:443".as_bytes();
+            assert_eq!(
+                &parsed_response.svc_host[..svc_host_bytes.len()],
+                svc_host_bytes
+            );
+            assert_eq!(parsed_response.parse_port(), 443);
+        }
+
+        #[test]
+        fn should_return_0_for_a_svc_host_without_a_port() {
+            let fetched_response = "returncd=MDA3&svchost=bi9h&datetime=MjAyMTAxMDIwMzA0MDU*";
+            let parsed_response = ServiceLocateData::from_fetched_response(fetched_response, 200)
+                .expect("Should have parsed the response");
+
+            assert_eq!(parsed_response.parse_port(), 0);
+        }
     }
 }
@@ -1,4 +1,9 @@
+pub mod add_friend;
 pub mod authentication;
 pub(crate) mod base_request;
+pub mod cache;
+pub mod http;
 pub mod locate;
+pub mod retry;
+pub mod return_code;
 pub(crate) mod utils;
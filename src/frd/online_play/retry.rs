@@ -0,0 +1,188 @@
+use super::http::{sleep_before_retry, NascHttpRequest};
+use crate::frd::{context::FriendServiceContext, result::FrdErrorCode};
+use core::cmp::{max, min};
+use ctr::result::CtrResult;
+
+/// How many times a NASC request is sent before a retryable failure is reported to the client.
+pub const DEFAULT_NASC_ATTEMPT_COUNT: usize = 3;
+
+/// The most attempts that can be configured.
+/// Requests are handled one at a time, so every retry keeps other clients waiting.
+pub const MAX_NASC_ATTEMPT_COUNT: usize = 5;
+
+/// How long to wait between attempts.
+/// This is kept short, since waiting blocks every other request.
+const NASC_RETRY_BACKOFF_NS: i64 = 50_000_000;
+
+/// Clamps a requested attempt count to at least one attempt, and at most [MAX_NASC_ATTEMPT_COUNT].
+pub fn clamp_nasc_attempt_count(attempt_count: usize) -> usize {
+    min(max(attempt_count, 1), MAX_NASC_ATTEMPT_COUNT)
+}
+
+/// Sends a NASC request until the response isn't asking for a retry,
+/// up to the context's configured attempt count.
+///
+/// An http context can only be downloaded once, so each attempt creates a new request.
+/// The session keeps the result of the last attempt.
+pub fn fetch_with_retry<C, F>(
+    context: &mut FriendServiceContext,
+    session_index: usize,
+    mut create_request: C,
+    fetch: F,
+) -> CtrResult
+where
+    C: FnMut(&FriendServiceContext) -> CtrResult<NascHttpRequest>,
+    F: Fn(&mut FriendServiceContext, usize, &NascHttpRequest) -> CtrResult,
+{
+    let attempt_count = clamp_nasc_attempt_count(context.nasc_attempt_count);
+
+    for attempt in 0..attempt_count {
+        let request = create_request(context)?;
+        fetch(context, session_index, &request)?;
+
+        let should_retry = context.session_contexts[session_index].last_response_result
+            == Err(FrdErrorCode::NascRetryLater);
+        if !should_retry || attempt + 1 == attempt_count {
+            break;
+        }
+
+        sleep_before_retry(NASC_RETRY_BACKOFF_NS);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frd::online_play::authentication::{
+        create_game_login_request, fetch_game_authentication,
+    };
+
+    // retry is 1 and returncd is "301"
+    const RETRY_RESPONSE: &str = "retry=MQ**&returncd=MzAx";
+    const AUTH_RESPONSE: &str = "locator=MTI3LjAuMC4xOjcwMDA*&retry=MA**&returncd=MDAx&token=AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8gISIjJCUmJygpKissLS4vMDE*&datetime=MjAyMTAxMDIwMzA0MDU*";
+
+    mod fetch_with_retry {
+        use super::*;
+
+        #[test]
+        fn should_retry_until_the_request_succeeds() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.accept_session(0);
+            let mut attempt_count = 0;
+
+            let result = fetch_with_retry(
+                &mut context,
+                0,
+                |context| {
+                    let request = create_game_login_request(context, 1, 0x00123400, 11, 2, "")?;
+                    let response = if attempt_count == 0 {
+                        RETRY_RESPONSE
+                    } else {
+                        AUTH_RESPONSE
+                    };
+                    request.set_response(200, response);
+                    attempt_count += 1;
+                    Ok(request)
+                },
+                fetch_game_authentication,
+            );
+
+            assert_eq!(result, Ok(()));
+            assert_eq!(attempt_count, 2);
+            assert_eq!(context.session_contexts[0].last_response_result, Ok(()));
+        }
+
+        #[test]
+        fn should_report_the_failure_after_the_last_attempt() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.accept_session(0);
+            let mut attempt_count = 0;
+
+            let result = fetch_with_retry(
+                &mut context,
+                0,
+                |context| {
+                    let request = create_game_login_request(context, 1, 0x00123400, 11, 2, "")?;
+                    request.set_response(200, RETRY_RESPONSE);
+                    attempt_count += 1;
+                    Ok(request)
+                },
+                fetch_game_authentication,
+            );
+
+            assert_eq!(result, Ok(()));
+            assert_eq!(attempt_count, DEFAULT_NASC_ATTEMPT_COUNT);
+            assert_eq!(
+                context.session_contexts[0].last_response_result,
+                Err(FrdErrorCode::NascRetryLater)
+            );
+        }
+
+        #[test]
+        fn should_use_the_configured_attempt_count() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.accept_session(0);
+            context.nasc_attempt_count = 1;
+            let mut attempt_count = 0;
+
+            fetch_with_retry(
+                &mut context,
+                0,
+                |context| {
+                    let request = create_game_login_request(context, 1, 0x00123400, 11, 2, "")?;
+                    request.set_response(200, RETRY_RESPONSE);
+                    attempt_count += 1;
+                    Ok(request)
+                },
+                fetch_game_authentication,
+            )
+            .unwrap();
+
+            assert_eq!(attempt_count, 1);
+        }
+
+        #[test]
+        fn should_not_retry_a_fatal_response() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.accept_session(0);
+            let mut attempt_count = 0;
+
+            fetch_with_retry(
+                &mut context,
+                0,
+                |context| {
+                    let request = create_game_login_request(context, 1, 0x00123400, 11, 2, "")?;
+                    // returncd is "102"
+                    request.set_response(200, "retry=MA**&returncd=MTAy");
+                    attempt_count += 1;
+                    Ok(request)
+                },
+                fetch_game_authentication,
+            )
+            .unwrap();
+
+            assert_eq!(attempt_count, 1);
+        }
+    }
+
+    mod clamp_nasc_attempt_count {
+        use super::*;
+
+        #[test]
+        fn should_keep_a_count_in_range() {
+            assert_eq!(clamp_nasc_attempt_count(2), 2);
+        }
+
+        #[test]
+        fn should_always_make_at_least_one_attempt() {
+            assert_eq!(clamp_nasc_attempt_count(0), 1);
+        }
+
+        #[test]
+        fn should_cap_the_count() {
+            assert_eq!(clamp_nasc_attempt_count(100), MAX_NASC_ATTEMPT_COUNT);
+        }
+    }
+}
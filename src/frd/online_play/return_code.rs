@@ -0,0 +1,77 @@
+use crate::frd::result::FrdErrorCode;
+
+/// How a NASC `returncd` should be handled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NascReturnCode {
+    /// 001 for a login, 002 for a created account, and 007 for a service locate.
+    Success,
+    /// 3xx codes are server side problems, such as maintenance, so the request can be sent again later.
+    Retryable,
+    /// Anything else, such as a 1xx code for a banned or unknown account.
+    Fatal,
+}
+
+impl NascReturnCode {
+    pub fn from_raw(return_code: u32) -> Self {
+        match return_code {
+            1 | 2 | 7 => Self::Success,
+            300..=399 => Self::Retryable,
+            _ => Self::Fatal,
+        }
+    }
+
+    /// The result clients get from GetLastResponseResult.
+    pub fn into_result(self) -> Result<(), FrdErrorCode> {
+        match self {
+            Self::Success => Ok(()),
+            Self::Retryable => Err(FrdErrorCode::NascRetryLater),
+            Self::Fatal => Err(FrdErrorCode::NascRequestRejected),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    mod from_raw {
+        use super::*;
+
+        #[test]
+        fn should_classify_success_codes() {
+            assert_eq!(NascReturnCode::from_raw(1), NascReturnCode::Success);
+            assert_eq!(NascReturnCode::from_raw(2), NascReturnCode::Success);
+            assert_eq!(NascReturnCode::from_raw(7), NascReturnCode::Success);
+        }
+
+        #[test]
+        fn should_classify_3xx_codes_as_retryable() {
+            assert_eq!(NascReturnCode::from_raw(300), NascReturnCode::Retryable);
+            assert_eq!(NascReturnCode::from_raw(399), NascReturnCode::Retryable);
+        }
+
+        #[test]
+        fn should_classify_other_codes_as_fatal() {
+            assert_eq!(NascReturnCode::from_raw(0), NascReturnCode::Fatal);
+            assert_eq!(NascReturnCode::from_raw(102), NascReturnCode::Fatal);
+            assert_eq!(NascReturnCode::from_raw(400), NascReturnCode::Fatal);
+        }
+    }
+
+    mod into_result {
+        use super::*;
+
+        #[test]
+        fn should_map_each_classification() {
+            assert_eq!(NascReturnCode::Success.into_result(), Ok(()));
+            assert_eq!(
+                NascReturnCode::Retryable.into_result(),
+                Err(FrdErrorCode::NascRetryLater)
+            );
+            assert_eq!(
+                NascReturnCode::Fatal.into_result(),
+                Err(FrdErrorCode::NascRequestRejected)
+            );
+        }
+    }
+}
@@ -1,21 +1,99 @@
-use alloc::{str, vec::Vec};
+use crate::frd::result::FrdErrorCode;
+use alloc::{str, string::String, vec::Vec};
 use core::str::FromStr;
 use ctr::{
+    os::get_time,
     result::{error, CtrResult},
     time::{FormattedTimestamp, SystemTimestamp},
     utils::base64_decode,
 };
 
+/// Large enough for the biggest documented NASC response (a login with a service token
+/// and locator), with room to spare for private servers that send longer tokens.
+pub const MAX_NASC_RESPONSE_SIZE: usize = 0x400;
+
+/// NASC responses are downloaded into a buffer one byte larger than the max size,
+/// so a response that doesn't fit can be detected rather than silently truncated.
+pub type NascResponseBuffer = [u8; MAX_NASC_RESPONSE_SIZE + 1];
+
+pub fn parse_nasc_response(buffer: &NascResponseBuffer) -> Result<&str, FrdErrorCode> {
+    if buffer[MAX_NASC_RESPONSE_SIZE] != 0 {
+        return Err(FrdErrorCode::NascResponseTooLarge);
+    }
+
+    // Anything after the first null is padding, so it shouldn't be validated
+    let response_bytes = &buffer[..MAX_NASC_RESPONSE_SIZE];
+    let response_len = response_bytes
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(MAX_NASC_RESPONSE_SIZE);
+
+    let response = str::from_utf8(&response_bytes[..response_len])
+        .map_err(|_| FrdErrorCode::InvalidNascResponse)?
+        .trim_end_matches("\r\n");
+
+    Ok(response)
+}
+
+pub fn get_current_unix_timestamp() -> u64 {
+    SystemTimestamp::new(get_time()).get_unix_timestamp()
+}
+
+/// The server time minus the console time, in seconds.
+/// This is negative when the console clock is ahead of the server.
+pub fn calculate_server_time_interval(
+    server_unix_timestamp: u64,
+    local_unix_timestamp: u64,
+) -> i64 {
+    (server_unix_timestamp as i64).saturating_sub(local_unix_timestamp as i64)
+}
+
+/// Formats a mac address the way NASC expects it, as 12 uppercase hex characters without separators.
+pub fn normalize_mac_address(mac_address: &str) -> CtrResult<String> {
+    let normalized_mac_address: String = mac_address
+        .chars()
+        .filter(|character| !matches!(character, ':' | '-' | '.'))
+        .map(|character| character.to_ascii_uppercase())
+        .collect();
+
+    let is_valid = normalized_mac_address.len() == 12
+        && normalized_mac_address
+            .chars()
+            .all(|character| character.is_ascii_hexdigit());
+
+    if is_valid {
+        Ok(normalized_mac_address)
+    } else {
+        Err(error::invalid_value())
+    }
+}
+
+/// Splits an address into its host and port.
+///
+/// IPv6 hosts need to be bracketed, e.g. `[::1]:7000`, since they contain colons.
 pub fn parse_address(full_address: &str) -> CtrResult<(&str, u32)> {
-    let colon = char::from_str(":").unwrap();
-    let mut split_address = full_address.split(colon);
-    let address = split_address.next();
-    let port = split_address.next();
+    let (address, port) = match full_address.strip_prefix('[') {
+        Some(bracketed_address) => {
+            let (address, remaining) = bracketed_address
+                .split_once(']')
+                .ok_or_else(error::invalid_value)?;
+            let port = remaining
+                .strip_prefix(':')
+                .ok_or_else(error::invalid_value)?;
+            (address, port)
+        }
+        None => full_address
+            .split_once(':')
+            .ok_or_else(error::invalid_value)?,
+    };
+
+    let port: u32 = port.parse().map_err(|_| error::invalid_value())?;
 
-    match (address, port) {
-        (Some(address), Some(port)) => Ok((address, port.parse()?)),
-        _ => Err(error::invalid_value()),
+    if !(1..=65535).contains(&port) {
+        return Err(error::invalid_value());
     }
+
+    Ok((address, port))
 }
 
 pub fn parse_datetime(datetime: &str) -> CtrResult<SystemTimestamp> {
@@ -53,3 +131,132 @@ pub fn parse_datetime_from_base64(base64: &str) -> CtrResult<SystemTimestamp> {
     let decoded_str = str::from_utf8(&decoded_bytes)?;
     parse_datetime(decoded_str)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    mod parse_nasc_response {
+        use super::*;
+
+        fn create_response_buffer(response: &[u8]) -> NascResponseBuffer {
+            let mut buffer = [0; MAX_NASC_RESPONSE_SIZE + 1];
+            buffer[..response.len()].copy_from_slice(response);
+            buffer
+        }
+
+        #[test]
+        fn should_trim_the_response() {
+            let buffer = create_response_buffer(b"returncd=MDAx\r\n");
+            let result = parse_nasc_response(&buffer);
+            assert_eq!(result, Ok("returncd=MDAx"));
+        }
+
+        #[test]
+        fn should_accept_a_response_that_fills_the_max_size() {
+            let buffer = create_response_buffer(&[b'a'; MAX_NASC_RESPONSE_SIZE]);
+            let result = parse_nasc_response(&buffer).expect("Should have parsed");
+            assert_eq!(result.len(), MAX_NASC_RESPONSE_SIZE);
+        }
+
+        #[test]
+        fn should_accept_a_response_larger_than_the_game_authentication_data() {
+            let buffer = create_response_buffer(&[b'a'; 0x200]);
+            let result = parse_nasc_response(&buffer).expect("Should have parsed");
+            assert_eq!(result.len(), 0x200);
+        }
+
+        #[test]
+        fn should_error_if_the_response_is_too_large() {
+            let buffer = create_response_buffer(&[b'a'; MAX_NASC_RESPONSE_SIZE + 1]);
+            let result = parse_nasc_response(&buffer);
+            assert_eq!(result, Err(FrdErrorCode::NascResponseTooLarge));
+        }
+
+        #[test]
+        fn should_error_if_the_response_is_not_utf8() {
+            let buffer = create_response_buffer(&[0xff, 0xfe]);
+            let result = parse_nasc_response(&buffer);
+            assert_eq!(result, Err(FrdErrorCode::InvalidNascResponse));
+        }
+
+        #[test]
+        fn should_ignore_invalid_bytes_after_the_null_terminator() {
+            let buffer = create_response_buffer(b"returncd=MDAx\r\n\0\xff\xfe");
+            let result = parse_nasc_response(&buffer);
+            assert_eq!(result, Ok("returncd=MDAx"));
+        }
+    }
+
+    mod calculate_server_time_interval {
+        use super::*;
+
+        #[test]
+        fn should_be_positive_when_the_server_is_ahead() {
+            let result = calculate_server_time_interval(1_600_000_030, 1_600_000_000);
+            assert_eq!(result, 30);
+        }
+
+        #[test]
+        fn should_be_negative_when_the_console_is_ahead() {
+            let result = calculate_server_time_interval(1_600_000_000, 1_600_000_030);
+            assert_eq!(result, -30);
+        }
+    }
+
+    mod normalize_mac_address {
+        use super::*;
+
+        #[test]
+        fn should_strip_colons_and_uppercase() {
+            let result = normalize_mac_address("01:23:45:67:89:ab");
+            assert_eq!(result, Ok("0123456789AB".into()));
+        }
+
+        #[test]
+        fn should_return_an_error_for_an_invalid_mac_address() {
+            assert!(normalize_mac_address("0123456789AG").is_err());
+            assert!(normalize_mac_address("0123456789").is_err());
+        }
+    }
+
+    mod parse_address {
+        use super::*;
+
+        #[test]
+        fn should_parse_an_ipv4_address() {
+            let result = parse_address("192.168.1.1:7000").expect("Should have parsed");
+            assert_eq!(result, ("192.168.1.1", 7000));
+        }
+
+        #[test]
+        fn should_parse_a_bracketed_ipv6_address() {
+            let result = parse_address("[::1]:7000").expect("Should have parsed");
+            assert_eq!(result, ("::1", 7000));
+        }
+
+        #[test]
+        fn should_error_if_the_port_is_out_of_range() {
+            assert_eq!(
+                parse_address("192.168.1.1:65536"),
+                Err(error::invalid_value())
+            );
+            assert_eq!(parse_address("192.168.1.1:0"), Err(error::invalid_value()));
+        }
+
+        #[test]
+        fn should_error_if_the_port_is_malformed() {
+            assert_eq!(
+                parse_address("192.168.1.1:70a0"),
+                Err(error::invalid_value())
+            );
+            assert_eq!(parse_address("::1:7000"), Err(error::invalid_value()));
+        }
+
+        #[test]
+        fn should_error_if_there_is_no_port() {
+            assert_eq!(parse_address("192.168.1.1"), Err(error::invalid_value()));
+            assert_eq!(parse_address("[::1]"), Err(error::invalid_value()));
+        }
+    }
+}
@@ -2,7 +2,7 @@ use ctr::result::ResultCode;
 use num_enum::IntoPrimitive;
 
 // TODO: Replace these with proper ctr::result::ResultCodes.
-#[derive(Debug, PartialEq, Eq, IntoPrimitive)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, IntoPrimitive)]
 #[repr(u32)]
 pub enum FrdErrorCode {
     InvalidPointer = 0xe0e0c7f6,
@@ -14,6 +14,18 @@ pub enum FrdErrorCode {
     InvalidCommand = 0xd900182f,
     InvalidAccountSaveFile = 0xc880c4ed,
     MissingData = 0xc8a0c7ef,
+    // Custom, not present in the official sysmodule, using descriptions from 0x200
+    // so they don't collide with any of the known official results
+    NascRequestFailed = 0xd900c600,
+    NascResponseTooLarge = 0xd900c601,
+    InvalidNascResponse = 0xd900c602,
+    WiFiBusy = 0xd900c603,
+    WiFiConnectionFailed = 0xd900c604,
+    NascRetryLater = 0xd900c605,
+    NascRequestRejected = 0xd900c606,
+    FriendListFull = 0xd900c607,
+    RequestingProcessGone = 0xd900c608,
+    GameAuthenticationExpired = 0xd900c609,
 }
 
 impl FrdErrorCode {
@@ -1,7 +1,8 @@
+use super::string::read_utf16le_field;
 use crate::frd::result::FrdErrorCode;
 use alloc::{format, string::String};
-use core::convert::TryInto;
-use ctr::{result::CtrResult, utils::convert::bytes_to_utf16le_string};
+use ctr::result::CtrResult;
+use no_std_io::{EndianRead, EndianWrite, Reader};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[repr(u8)]
@@ -11,13 +12,15 @@ pub enum NascEnvironment {
     Dev = 2,
 }
 
-impl From<u8> for NascEnvironment {
-    fn from(byte: u8) -> Self {
+impl TryFrom<u8> for NascEnvironment {
+    type Error = FrdErrorCode;
+
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
         match byte {
-            0 => Self::Prod,
-            1 => Self::Test,
-            2 => Self::Dev,
-            _ => Self::Prod,
+            0 => Ok(Self::Prod),
+            1 => Ok(Self::Test),
+            2 => Ok(Self::Dev),
+            _ => Err(FrdErrorCode::InvalidAccountSaveFile),
         }
     }
 }
@@ -33,60 +36,346 @@ pub struct AccountConfig {
     pub server_type_2: u8,
 }
 
+pub const ACCOUNT_MAGIC: u64 = 0x2010102143415046;
+
+/// The hmac is 8 hex characters, which fills its field apart from the null terminator.
+pub const PRINCIPAL_ID_HMAC_LEN: usize = 8;
+
+/// Accounts that were never registered online don't have an hmac yet, so it can also be empty.
+fn is_valid_principal_id_hmac(principal_id_hmac: &str) -> bool {
+    principal_id_hmac.is_empty() || principal_id_hmac.len() == PRINCIPAL_ID_HMAC_LEN
+}
+
+/// The second server type is written as a single digit in the NASC `servertype` field.
+const MAX_SERVER_TYPE_2: u8 = 9;
+
+/// Server types are only valid if NASC knows what to do with them.
+fn is_valid_server_types(server_type_1: u8, server_type_2: u8) -> bool {
+    get_server_type_letter(server_type_1).is_some() && server_type_2 <= MAX_SERVER_TYPE_2
+}
+
+pub const ACCOUNT_FILE_SIZE: usize = 88;
+
+/// The raw 88 byte layout of the account save file.
+#[derive(EndianRead, EndianWrite)]
+struct RawAccountConfig {
+    magic: u64,
+    unk1: [u8; 8],
+    local_account_id: u32,
+    principal_id: u32,
+    local_friend_code: u64,
+    nex_password: [u8; 32],
+    unk2: [u8; 2],
+    principal_id_hmac: [u8; 18],
+    nasc_environment: u8,
+    server_type_1: u8,
+    server_type_2: u8,
+    unk3: u8,
+}
+
 impl AccountConfig {
-    pub fn try_from_le_bytes(raw_data: [u8; 88]) -> CtrResult<Self> {
-        let header_bytes = raw_data[..8].try_into().unwrap();
+    pub fn try_from_le_bytes(raw_data: &[u8]) -> CtrResult<Self> {
+        let raw_account_config: RawAccountConfig = raw_data
+            .read_le(0)
+            .map_err(|_| FrdErrorCode::InvalidAccountSaveFile)?;
 
-        if u64::from_le_bytes(header_bytes) != 0x2010102143415046 {
+        if raw_account_config.magic != ACCOUNT_MAGIC {
             return Err(FrdErrorCode::InvalidAccountSaveFile.into());
         }
 
-        let local_account_id_bytes = raw_data[16..20].try_into().unwrap();
-        let principal_id_bytes = raw_data[20..24].try_into().unwrap();
-        let local_friend_code_bytes = raw_data[24..32].try_into().unwrap();
+        let principal_id_hmac = read_utf16le_field(
+            &raw_account_config.principal_id_hmac,
+            "account.principal_id_hmac",
+        );
+
+        // NASC rejects a bad hmac without saying why, so catch it here instead
+        if !is_valid_principal_id_hmac(&principal_id_hmac) {
+            return Err(FrdErrorCode::InvalidAccountSaveFile.into());
+        }
+
+        if !is_valid_server_types(
+            raw_account_config.server_type_1,
+            raw_account_config.server_type_2,
+        ) {
+            return Err(FrdErrorCode::InvalidAccountSaveFile.into());
+        }
 
         Ok(Self {
-            local_account_id: u32::from_le_bytes(local_account_id_bytes),
-            principal_id: u32::from_le_bytes(principal_id_bytes),
-            local_friend_code: u64::from_le_bytes(local_friend_code_bytes),
-            nex_password: bytes_to_utf16le_string(&raw_data[32..64])?,
-            principal_id_hmac: bytes_to_utf16le_string(&raw_data[66..84])?,
-            nasc_environment: raw_data[84].into(),
-            server_type_1: raw_data[85],
-            server_type_2: raw_data[86],
+            local_account_id: raw_account_config.local_account_id,
+            principal_id: raw_account_config.principal_id,
+            local_friend_code: raw_account_config.local_friend_code,
+            nex_password: read_utf16le_field(
+                &raw_account_config.nex_password,
+                "account.nex_password",
+            ),
+            principal_id_hmac,
+            nasc_environment: raw_account_config.nasc_environment.try_into()?,
+            server_type_1: raw_account_config.server_type_1,
+            server_type_2: raw_account_config.server_type_2,
         })
     }
 
-    pub fn get_server_type_string(&self) -> String {
-        let server_type_1_letter = match self.server_type_1 {
-            0 => "L",
-            1 => "C",
-            2 => "S",
-            3 => "D",
-            4 => "I",
-            5 => "T",
-            7 => "J",
-            8 => "X",
-            9 => "A",
-            10 => "B",
-            11 => "C",
-            12 => "D",
-            13 => "E",
-            14 => "F",
-            15 => "G",
-            16 => "H",
-            17 => "I",
-            18 => "J",
-            19 => "K",
-            20 => "L",
-            21 => "M",
-            22 => "N",
-            23 => "O",
-            24 => "P",
-            25 => "Q",
-            _ => "U",
-        };
-
-        format!("{}{}", server_type_1_letter, self.server_type_2)
+    /// Returns the nasc environment and server types, or an error if a server type is out of range.
+    pub fn get_server_types(&self) -> Result<(NascEnvironment, u8, u8), FrdErrorCode> {
+        if !is_valid_server_types(self.server_type_1, self.server_type_2) {
+            return Err(FrdErrorCode::InvalidAccountSaveFile);
+        }
+
+        Ok((
+            self.nasc_environment,
+            self.server_type_1,
+            self.server_type_2,
+        ))
+    }
+
+    pub fn get_server_type_string(&self) -> CtrResult<String> {
+        let server_type_1_letter = get_server_type_letter(self.server_type_1)
+            .ok_or(FrdErrorCode::InvalidAccountSaveFile)?;
+
+        Ok(format!("{}{}", server_type_1_letter, self.server_type_2))
+    }
+}
+
+/// Maps a server type to the letter used by the NASC `servertype` field.
+///
+/// 6 is a legitimate "U", while values above 25 aren't known and return None
+/// so they aren't confused with it.
+pub fn get_server_type_letter(server_type: u8) -> Option<&'static str> {
+    let letter = match server_type {
+        0 => "L",
+        1 => "C",
+        2 => "S",
+        3 => "D",
+        4 => "I",
+        5 => "T",
+        6 => "U",
+        7 => "J",
+        8 => "X",
+        9 => "A",
+        10 => "B",
+        11 => "C",
+        12 => "D",
+        13 => "E",
+        14 => "F",
+        15 => "G",
+        16 => "H",
+        17 => "I",
+        18 => "J",
+        19 => "K",
+        20 => "L",
+        21 => "M",
+        22 => "N",
+        23 => "O",
+        24 => "P",
+        25 => "Q",
+        _ => return None,
+    };
+
+    Some(letter)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::string::ToString;
+
+    fn create_account_config(server_type_1: u8, server_type_2: u8) -> AccountConfig {
+        AccountConfig {
+            local_account_id: 1,
+            principal_id: 0xaabbccdd,
+            local_friend_code: 0x38aabbccdd,
+            nex_password: "password".to_string(),
+            principal_id_hmac: "0123abcd".to_string(),
+            nasc_environment: NascEnvironment::Prod,
+            server_type_1,
+            server_type_2,
+        }
+    }
+
+    fn create_account_bytes() -> [u8; 88] {
+        let mut bytes = [0; 88];
+        bytes[..8].copy_from_slice(&ACCOUNT_MAGIC.to_le_bytes());
+        bytes[20..24].copy_from_slice(&0xaabbccddu32.to_le_bytes());
+        bytes[32..34].copy_from_slice(&[0x70, 0x00]);
+        bytes[85..87].copy_from_slice(&[2, 2]);
+        bytes
+    }
+
+    mod try_from_le_bytes {
+        use super::*;
+
+        const HMAC_OFFSET: usize = 66;
+
+        fn write_hmac(bytes: &mut [u8], hmac: &[u8]) {
+            for (index, char) in hmac.iter().enumerate() {
+                bytes[HMAC_OFFSET + (index * 2)] = *char;
+            }
+        }
+
+        #[test]
+        fn should_parse_a_valid_account() {
+            let account_config = AccountConfig::try_from_le_bytes(&create_account_bytes())
+                .expect("Should have parsed the account");
+
+            assert_eq!(account_config.principal_id, 0xaabbccdd);
+            assert_eq!(account_config.nex_password, "p");
+            assert_eq!(account_config.server_type_1, 2);
+            assert_eq!(account_config.server_type_2, 2);
+        }
+
+        #[test]
+        fn should_parse_a_full_length_hmac() {
+            let mut bytes = create_account_bytes();
+            write_hmac(&mut bytes, b"0123abcd");
+
+            let account_config =
+                AccountConfig::try_from_le_bytes(&bytes).expect("Should have parsed the account");
+
+            assert_eq!(account_config.principal_id_hmac, "0123abcd");
+        }
+
+        #[test]
+        fn should_return_an_error_for_a_wrong_length_hmac() {
+            let mut bytes = create_account_bytes();
+            write_hmac(&mut bytes, b"0123a");
+
+            let result = AccountConfig::try_from_le_bytes(&bytes).err();
+            assert_eq!(
+                result,
+                Some(FrdErrorCode::InvalidAccountSaveFile.into_result_code())
+            );
+        }
+
+        #[test]
+        fn should_return_an_error_for_an_out_of_range_nasc_environment() {
+            let mut bytes = create_account_bytes();
+            bytes[84] = 3;
+
+            let result = AccountConfig::try_from_le_bytes(&bytes).err();
+            assert_eq!(
+                result,
+                Some(FrdErrorCode::InvalidAccountSaveFile.into_result_code())
+            );
+        }
+
+        #[test]
+        fn should_return_an_error_for_an_out_of_range_server_type() {
+            let mut bytes = create_account_bytes();
+            bytes[85] = 26;
+
+            let result = AccountConfig::try_from_le_bytes(&bytes).err();
+            assert_eq!(
+                result,
+                Some(FrdErrorCode::InvalidAccountSaveFile.into_result_code())
+            );
+
+            let mut bytes = create_account_bytes();
+            bytes[86] = 10;
+
+            let result = AccountConfig::try_from_le_bytes(&bytes).err();
+            assert_eq!(
+                result,
+                Some(FrdErrorCode::InvalidAccountSaveFile.into_result_code())
+            );
+        }
+
+        #[test]
+        fn should_return_an_error_for_a_short_buffer() {
+            let bytes = create_account_bytes();
+            let result = AccountConfig::try_from_le_bytes(&bytes[..40]).err();
+            assert_eq!(
+                result,
+                Some(FrdErrorCode::InvalidAccountSaveFile.into_result_code())
+            );
+        }
+
+        #[test]
+        fn should_return_an_error_for_an_invalid_magic() {
+            let mut bytes = create_account_bytes();
+            bytes[7] = 0;
+
+            let result = AccountConfig::try_from_le_bytes(&bytes).err();
+            assert_eq!(
+                result,
+                Some(FrdErrorCode::InvalidAccountSaveFile.into_result_code())
+            );
+        }
+    }
+
+    mod get_server_types {
+        use super::*;
+
+        #[test]
+        fn should_return_valid_server_types() {
+            let result = create_account_config(25, 9).get_server_types();
+            assert_eq!(result, Ok((NascEnvironment::Prod, 25, 9)));
+        }
+
+        #[test]
+        fn should_return_an_error_for_out_of_range_server_types() {
+            let result = create_account_config(26, 2).get_server_types();
+            assert_eq!(result, Err(FrdErrorCode::InvalidAccountSaveFile));
+
+            let result = create_account_config(2, 10).get_server_types();
+            assert_eq!(result, Err(FrdErrorCode::InvalidAccountSaveFile));
+        }
+    }
+
+    mod get_server_type_letter {
+        use super::*;
+
+        #[test]
+        fn should_return_l_for_0() {
+            assert_eq!(get_server_type_letter(0), Some("L"));
+        }
+
+        #[test]
+        fn should_return_t_for_5() {
+            assert_eq!(get_server_type_letter(5), Some("T"));
+        }
+
+        #[test]
+        fn should_return_u_for_6() {
+            assert_eq!(get_server_type_letter(6), Some("U"));
+        }
+
+        #[test]
+        fn should_return_j_for_7() {
+            assert_eq!(get_server_type_letter(7), Some("J"));
+        }
+
+        #[test]
+        fn should_return_q_for_25() {
+            assert_eq!(get_server_type_letter(25), Some("Q"));
+        }
+
+        #[test]
+        fn should_return_none_for_26() {
+            assert_eq!(get_server_type_letter(26), None);
+        }
+
+        #[test]
+        fn should_return_none_for_255() {
+            assert_eq!(get_server_type_letter(255), None);
+        }
+    }
+
+    mod get_server_type_string {
+        use super::*;
+
+        #[test]
+        fn should_combine_the_letter_and_second_server_type() {
+            let result = create_account_config(2, 2).get_server_type_string();
+            assert_eq!(result, Ok("S2".to_string()));
+        }
+
+        #[test]
+        fn should_return_an_error_for_an_unknown_server_type() {
+            let result = create_account_config(26, 2).get_server_type_string();
+            assert_eq!(
+                result,
+                Err(FrdErrorCode::InvalidAccountSaveFile.into_result_code())
+            );
+        }
     }
 }
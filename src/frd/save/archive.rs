@@ -0,0 +1,236 @@
+#[cfg(not(target_os = "horizon"))]
+use alloc::collections::BTreeMap;
+use alloc::{format, string::String, vec::Vec};
+use ctr::{
+    fs::{FsArchive, OpenFlags},
+    result::{error, CtrResult},
+};
+
+/// The file operations the save code needs from an archive.
+pub trait SaveArchive {
+    fn exists(&self, path: &str) -> bool;
+    fn read(&self, path: &str, offset: u64, size: usize) -> CtrResult<Vec<u8>>;
+    /// Creates or replaces the file at `path` with `data`.
+    fn write(&mut self, path: &str, data: &[u8]) -> CtrResult<()>;
+    fn rename(&mut self, old_path: &str, new_path: &str) -> CtrResult<()>;
+    fn delete(&mut self, path: &str) -> CtrResult<()>;
+}
+
+impl SaveArchive for FsArchive {
+    fn exists(&self, path: &str) -> bool {
+        self.open_file(&path.into(), OpenFlags::Read).is_ok()
+    }
+
+    fn read(&self, path: &str, offset: u64, size: usize) -> CtrResult<Vec<u8>> {
+        self.open_file(&path.into(), OpenFlags::Read)?
+            .read(offset, size)
+    }
+
+    fn write(&mut self, path: &str, data: &[u8]) -> CtrResult<()> {
+        let file = self.open_file(&path.into(), OpenFlags::ReadWriteCreate)?;
+        file.set_size(data.len() as u64)?;
+        file.write(0, data)?;
+        Ok(())
+    }
+
+    fn rename(&mut self, old_path: &str, new_path: &str) -> CtrResult<()> {
+        self.rename_file(&old_path.into(), &new_path.into())
+    }
+
+    fn delete(&mut self, path: &str) -> CtrResult<()> {
+        self.delete_file(&path.into())
+    }
+}
+
+fn get_temp_path(path: &str) -> String {
+    format!("{}.tmp", path)
+}
+
+/// Writes a file so a failure part way through never replaces a good file.
+///
+/// The data is written to a temp file and read back before it replaces the original,
+/// so the original is untouched until a complete copy of the new data exists.
+/// If the process dies after the original is deleted, the temp file is still intact
+/// and [recover_interrupted_write] puts it in place.
+///
+/// Requests from every session are handled one at a time, so two writes never share the temp file.
+pub fn atomic_write<A: SaveArchive>(archive: &mut A, path: &str, data: &[u8]) -> CtrResult<()> {
+    let temp_path = get_temp_path(path);
+    archive.write(&temp_path, data)?;
+
+    // A short write can't be read back at the full size, so it's treated like a mismatch
+    let is_complete = archive
+        .read(&temp_path, 0, data.len())
+        .map(|written_data| written_data == data)
+        .unwrap_or(false);
+    if !is_complete {
+        archive.delete(&temp_path)?;
+        return Err(error::invalid_value());
+    }
+
+    if archive.exists(path) {
+        archive.delete(path)?;
+    }
+
+    archive.rename(&temp_path, path)
+}
+
+/// Cleans up after an [atomic_write] that didn't finish.
+///
+/// If the original is missing, the temp file is a complete copy and replaces it.
+/// Otherwise the original is still good and the temp file is discarded.
+pub fn recover_interrupted_write<A: SaveArchive>(archive: &mut A, path: &str) -> CtrResult<()> {
+    let temp_path = get_temp_path(path);
+
+    if !archive.exists(&temp_path) {
+        return Ok(());
+    }
+
+    if archive.exists(path) {
+        archive.delete(&temp_path)
+    } else {
+        archive.rename(&temp_path, path)
+    }
+}
+
+/// An archive kept in memory so save code can be tested on the host.
+#[cfg(not(target_os = "horizon"))]
+#[derive(Default)]
+pub(crate) struct MemoryArchive {
+    pub files: BTreeMap<String, Vec<u8>>,
+    pub should_fail_delete: bool,
+    pub should_fail_rename: bool,
+    /// Cuts writes off after this many bytes, like a write interrupted part way through.
+    pub max_write_size: Option<usize>,
+}
+
+#[cfg(not(target_os = "horizon"))]
+impl SaveArchive for MemoryArchive {
+    fn exists(&self, path: &str) -> bool {
+        self.files.contains_key(path)
+    }
+
+    fn read(&self, path: &str, offset: u64, size: usize) -> CtrResult<Vec<u8>> {
+        let file = self.files.get(path).ok_or_else(error::invalid_value)?;
+        let start = offset as usize;
+        file.get(start..start + size)
+            .map(|bytes| bytes.to_vec())
+            .ok_or_else(error::invalid_value)
+    }
+
+    fn write(&mut self, path: &str, data: &[u8]) -> CtrResult<()> {
+        let write_size = self.max_write_size.unwrap_or(data.len()).min(data.len());
+        self.files.insert(path.into(), data[..write_size].to_vec());
+        Ok(())
+    }
+
+    fn rename(&mut self, old_path: &str, new_path: &str) -> CtrResult<()> {
+        if self.should_fail_rename {
+            return Err(error::invalid_value());
+        }
+
+        let file = self
+            .files
+            .remove(old_path)
+            .ok_or_else(error::invalid_value)?;
+        self.files.insert(new_path.into(), file);
+        Ok(())
+    }
+
+    fn delete(&mut self, path: &str) -> CtrResult<()> {
+        if self.should_fail_delete {
+            return Err(error::invalid_value());
+        }
+
+        self.files.remove(path).ok_or_else(error::invalid_value)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec;
+
+    mod atomic_write {
+        use super::*;
+
+        #[test]
+        fn should_replace_the_original_file() {
+            let mut archive = MemoryArchive::default();
+            archive.write("/1/mydata", &[1, 2, 3]).unwrap();
+
+            atomic_write(&mut archive, "/1/mydata", &[4, 5, 6]).expect("Should have written");
+
+            assert_eq!(archive.files.get("/1/mydata"), Some(&vec![4, 5, 6]));
+            assert_eq!(archive.files.get("/1/mydata.tmp"), None);
+        }
+
+        #[test]
+        fn should_create_a_missing_file() {
+            let mut archive = MemoryArchive::default();
+            atomic_write(&mut archive, "/1/mydata", &[4, 5, 6]).expect("Should have written");
+            assert_eq!(archive.files.get("/1/mydata"), Some(&vec![4, 5, 6]));
+        }
+
+        #[test]
+        fn should_keep_the_original_intact_if_a_failure_happens_after_the_temp_write() {
+            let mut archive = MemoryArchive {
+                should_fail_delete: true,
+                ..Default::default()
+            };
+            archive.write("/1/mydata", &[1, 2, 3]).unwrap();
+
+            let result = atomic_write(&mut archive, "/1/mydata", &[4, 5, 6]);
+
+            assert!(result.is_err());
+            assert_eq!(archive.files.get("/1/mydata"), Some(&vec![1, 2, 3]));
+        }
+
+        #[test]
+        fn should_keep_the_original_intact_if_the_temp_write_is_cut_off() {
+            let mut archive = MemoryArchive::default();
+            archive.write("/1/mydata", &[1, 2, 3]).unwrap();
+            archive.max_write_size = Some(2);
+
+            let result = atomic_write(&mut archive, "/1/mydata", &[4, 5, 6]);
+
+            assert!(result.is_err());
+            assert_eq!(archive.files.get("/1/mydata"), Some(&vec![1, 2, 3]));
+            assert_eq!(archive.files.get("/1/mydata.tmp"), None);
+        }
+
+        #[test]
+        fn should_leave_a_recoverable_temp_file_if_interrupted_before_the_rename() {
+            let mut archive = MemoryArchive {
+                should_fail_rename: true,
+                ..Default::default()
+            };
+            archive.write("/1/mydata", &[1, 2, 3]).unwrap();
+
+            let result = atomic_write(&mut archive, "/1/mydata", &[4, 5, 6]);
+            assert!(result.is_err());
+            assert_eq!(archive.files.get("/1/mydata.tmp"), Some(&vec![4, 5, 6]));
+
+            archive.should_fail_rename = false;
+            recover_interrupted_write(&mut archive, "/1/mydata").unwrap();
+            assert_eq!(archive.files.get("/1/mydata"), Some(&vec![4, 5, 6]));
+        }
+    }
+
+    mod recover_interrupted_write {
+        use super::*;
+
+        #[test]
+        fn should_discard_the_temp_file_when_the_original_is_intact() {
+            let mut archive = MemoryArchive::default();
+            archive.write("/1/mydata", &[1, 2, 3]).unwrap();
+            archive.write("/1/mydata.tmp", &[4, 5]).unwrap();
+
+            recover_interrupted_write(&mut archive, "/1/mydata").unwrap();
+
+            assert_eq!(archive.files.get("/1/mydata"), Some(&vec![1, 2, 3]));
+            assert_eq!(archive.files.get("/1/mydata.tmp"), None);
+        }
+    }
+}
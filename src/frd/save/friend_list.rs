@@ -1,13 +1,48 @@
+use super::archive::SaveArchive;
+use crate::{
+    frd::{
+        result::FrdErrorCode,
+        utils::{get_character_set_id, get_current_formatted_timestamp},
+    },
+    log,
+};
+use alloc::{format, vec::Vec};
 use ctr::{
     frd::{
         FriendComment, FriendInfo, FriendKey, FriendProfile, GameKey, Mii, ScreenName,
         SomeFriendThing, TrivialCharacterSet,
     },
+    result::CtrResult,
     time::FormattedTimestamp,
 };
-use no_std_io::{EndianRead, EndianWrite};
+use no_std_io::{EndianRead, EndianWrite, Reader};
 
+/// The most friends a friend list can hold.
 pub const MAX_FRIEND_COUNT: usize = 100;
+pub const FRIEND_LIST_HEADER_SIZE: usize = 16;
+pub const FRIEND_ENTRY_SIZE: usize = 0x100;
+/// "FPFL" followed by the format version, like the mydata and account magics.
+/// Entries are only known to be 0x100 bytes for this version.
+pub const FRIEND_LIST_MAGIC: u64 = 0x201010214c465046;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, EndianRead, EndianWrite)]
+#[repr(C)]
+pub struct FriendListHeader {
+    pub magic: u64,
+    pub unk1: u32,
+    pub unk2: u32,
+}
+
+impl FriendListHeader {
+    /// Checks the magic and version, so a different format isn't read as garbage.
+    pub fn validate(&self) -> Result<(), FrdErrorCode> {
+        if self.magic != FRIEND_LIST_MAGIC {
+            return Err(FrdErrorCode::InvalidFriendListOrMyDataSaveFile);
+        }
+
+        Ok(())
+    }
+}
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Default, EndianRead, EndianWrite)]
 #[repr(C)]
@@ -41,14 +76,7 @@ impl From<FriendEntry> for FriendInfo {
             unk1: [0, 0, 0],
             unk2: 0,
             unk3: SomeFriendThing {
-                friend_profile: FriendProfile {
-                    region: friend_entry.friend_profile.region,
-                    country: friend_entry.friend_profile.country,
-                    area: friend_entry.friend_profile.area,
-                    language: friend_entry.friend_profile.language,
-                    platform: friend_entry.friend_profile.platform,
-                    padding: [0; 3],
-                },
+                friend_profile: friend_entry.get_profile(),
                 favorite_game: friend_entry.favorite_game,
                 unk2: 0,
                 comment: friend_entry.comment,
@@ -66,11 +94,354 @@ impl From<FriendEntry> for FriendInfo {
 const FRIEND_ATTRIBUTE: [u32; 6] = [0, 3, 0, 1, 1, 0];
 
 impl FriendEntry {
+    /// Creates a friend added just now, with everything that isn't given left empty.
+    pub fn new(
+        friend_key: FriendKey,
+        screen_name: ScreenName,
+        friend_profile: FriendProfile,
+    ) -> Self {
+        let added_at = get_current_formatted_timestamp();
+
+        Self {
+            friend_key,
+            friend_relationship: 3,
+            friend_profile,
+            screen_name,
+            timestamp1: added_at,
+            timestamp2: added_at,
+            timestamp1_2: added_at,
+            timestamp2_2: added_at,
+            ..Default::default()
+        }
+    }
+
+    /// An unused slot is zeroed, so it has neither a principal id nor a local friend code.
+    pub fn is_empty(&self) -> bool {
+        self.friend_key.principal_id == 0 && self.friend_key.local_friend_code == 0
+    }
+
+    /// Unknown relationships are treated as 3, the same as a full friend.
     pub fn get_attribute(&self) -> u32 {
-        if self.friend_relationship > 5 {
-            return 3;
+        FRIEND_ATTRIBUTE
+            .get(self.friend_relationship as usize)
+            .copied()
+            .unwrap_or(3)
+    }
+
+    /// The profile without the padding bytes, so stale save data isn't sent to clients.
+    pub fn get_profile(&self) -> FriendProfile {
+        FriendProfile {
+            padding: [0; 3],
+            ..self.friend_profile
+        }
+    }
+
+    pub fn is_character_set_compatible(&self, character_set: u32) -> bool {
+        get_character_set_id(self.character_set) as u32 == character_set
+    }
+
+    /// Creates the friend info for a client using the given character set.
+    /// Text that can't be shown in that character set is blanked.
+    pub fn get_friend_info(&self, character_set: u32) -> FriendInfo {
+        let mut friend_info = FriendInfo::from(*self);
+
+        if !self.is_character_set_compatible(character_set) {
+            friend_info.screen_name = Default::default();
+            friend_info.unk3.comment = Default::default();
+        }
+
+        friend_info
+    }
+}
+
+fn read_friend_entry<A: SaveArchive>(archive: &A, path: &str, index: usize) -> Option<FriendEntry> {
+    let offset = (index * FRIEND_ENTRY_SIZE) + FRIEND_LIST_HEADER_SIZE;
+    archive
+        .read(path, offset as u64, FRIEND_ENTRY_SIZE)
+        .ok()?
+        .read_le(0)
+        .ok()
+}
+
+pub fn read_friend_list<A: SaveArchive>(archive: &A, path: &str) -> CtrResult<Vec<FriendEntry>> {
+    let header = archive
+        .read(path, 0, FRIEND_LIST_HEADER_SIZE)?
+        .read_le::<FriendListHeader>(0)
+        .ok();
+
+    // The header isn't confirmed against retail saves, so an unexpected one
+    // shouldn't keep the sysmodule from starting
+    if !matches!(header, Some(header) if header.validate().is_ok()) {
+        log::debug(&format!("Unexpected friend list header {:?}", header));
+    }
+
+    let mut friend_list = Vec::with_capacity(MAX_FRIEND_COUNT);
+
+    // The list ends wherever the file does
+    for index in 0..MAX_FRIEND_COUNT {
+        // Friends are stored contiguously, so the first empty slot is the end of the list
+        let friend_entry = match read_friend_entry(archive, path, index) {
+            Some(friend_entry) if !friend_entry.is_empty() => friend_entry,
+            _ => break,
+        };
+
+        let principal_id = friend_entry.friend_key.principal_id;
+        let is_duplicate = friend_list
+            .iter()
+            .any(|friend: &FriendEntry| friend.friend_key.principal_id == principal_id);
+
+        // Lookups only ever find the first match, so keep the list unique
+        if is_duplicate {
+            log::debug(&format!("Skipping duplicate friend {:08x}", principal_id));
+        } else {
+            friend_list.push(friend_entry);
+        }
+    }
+
+    Ok(friend_list)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frd::{context::FriendServiceContext, save::archive::MemoryArchive};
+    use alloc::vec;
+    use no_std_io::Writer;
+
+    fn create_friend_entry(character_set_id: u8) -> FriendEntry {
+        FriendEntry {
+            friend_key: FriendKey {
+                principal_id: 0x11223344,
+                padding: 0,
+                local_friend_code: 0x0011223344,
+            },
+            comment: FriendComment::new([0x41; 17]),
+            screen_name: ScreenName::new([0x42; 11]),
+            character_set: vec![character_set_id].read_le(0).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    mod new {
+        use super::*;
+
+        #[test]
+        fn should_set_defaults() {
+            let friend_key = FriendKey {
+                principal_id: 0x55667788,
+                padding: 0,
+                local_friend_code: 0,
+            };
+            let screen_name = ScreenName::new([0x42; 11]);
+
+            let friend = FriendEntry::new(friend_key, screen_name, FriendProfile::default());
+
+            assert_eq!(friend.friend_key, friend_key);
+            assert_eq!(friend.screen_name, screen_name);
+            assert_eq!(friend.friend_relationship, 3);
+            assert_eq!(friend.timestamp1, friend.timestamp2);
+            assert_eq!(friend.last_online, FormattedTimestamp::default());
+            assert_eq!(friend.comment, FriendComment::default());
+            assert_eq!(friend.mii, Mii::default());
         }
+    }
+
+    mod is_empty {
+        use super::*;
+
+        #[test]
+        fn should_be_true_for_a_zeroed_entry() {
+            assert!(FriendEntry::default().is_empty());
+        }
+
+        #[test]
+        fn should_be_false_for_a_friend() {
+            assert!(!create_friend_entry(0).is_empty());
+        }
+    }
+
+    mod get_attribute {
+        use super::*;
+
+        #[test]
+        fn should_map_every_relationship() {
+            let attributes: Vec<u32> = (0..=7)
+                .map(|friend_relationship| {
+                    let friend = FriendEntry {
+                        friend_relationship,
+                        ..Default::default()
+                    };
+                    friend.get_attribute()
+                })
+                .collect();
+
+            assert_eq!(attributes, [0, 3, 0, 1, 1, 0, 3, 3]);
+        }
+    }
+
+    mod get_profile {
+        use super::*;
+
+        #[test]
+        fn should_zero_the_padding() {
+            let mut friend = create_friend_entry(1);
+            friend.friend_profile = FriendProfile {
+                region: 1,
+                country: 49,
+                area: 2,
+                language: 1,
+                platform: 2,
+                padding: [0xaa, 0xbb, 0xcc],
+            };
+
+            let profile = friend.get_profile();
+
+            assert_eq!(profile.padding, [0; 3]);
+            assert_eq!(profile.region, 1);
+            assert_eq!(profile.country, 49);
+            assert_eq!(profile.area, 2);
+            assert_eq!(profile.language, 1);
+            assert_eq!(profile.platform, 2);
+        }
+    }
+
+    mod get_friend_info {
+        use super::*;
+
+        #[test]
+        fn should_keep_text_for_a_matching_character_set() {
+            let friend = create_friend_entry(1);
+            let friend_info = friend.get_friend_info(1);
+
+            assert_eq!(friend_info.screen_name, friend.screen_name);
+            assert_eq!(friend_info.unk3.comment, friend.comment);
+        }
+
+        #[test]
+        fn should_blank_text_for_a_different_character_set() {
+            let friend = create_friend_entry(1);
+            let friend_info = friend.get_friend_info(0);
+
+            assert_eq!(friend_info.screen_name, ScreenName::default());
+            assert_eq!(friend_info.unk3.comment, FriendComment::default());
+            assert_eq!(friend_info.friend_key, friend.friend_key);
+        }
+    }
+
+    fn create_friends(count: u32) -> Vec<FriendEntry> {
+        (0..count)
+            .map(|index| {
+                let mut friend = create_friend_entry(1);
+                friend.friend_key.principal_id += index;
+                friend
+            })
+            .collect()
+    }
 
-        FRIEND_ATTRIBUTE[self.friend_relationship as usize]
+    fn create_friend_list_file(friends: &[FriendEntry]) -> Vec<u8> {
+        let header = FriendListHeader {
+            magic: FRIEND_LIST_MAGIC,
+            ..Default::default()
+        };
+        let mut bytes = vec![];
+        bytes.checked_write_le(0, &header);
+
+        for (index, friend) in friends.iter().enumerate() {
+            let offset = FRIEND_LIST_HEADER_SIZE + (index * FRIEND_ENTRY_SIZE);
+            bytes.checked_write_le(offset, friend);
+        }
+
+        bytes
+    }
+
+    mod read_friend_list {
+        use super::*;
+
+        #[test]
+        fn should_load_at_most_the_max_friend_count() {
+            let mut archive = MemoryArchive::default();
+            archive.files.insert(
+                "/1/friendlist".into(),
+                create_friend_list_file(&create_friends(MAX_FRIEND_COUNT as u32 + 5)),
+            );
+
+            let friend_list =
+                read_friend_list(&archive, "/1/friendlist").expect("Should have read");
+
+            assert_eq!(friend_list, create_friends(MAX_FRIEND_COUNT as u32));
+        }
+
+        #[test]
+        fn should_still_load_friends_with_an_unknown_header() {
+            let mut bytes = create_friend_list_file(&create_friends(3));
+            bytes[4..8].copy_from_slice(&0x20201021u32.to_le_bytes());
+            let mut archive = MemoryArchive::default();
+            archive.files.insert("/1/friendlist".into(), bytes);
+
+            let friend_list =
+                read_friend_list(&archive, "/1/friendlist").expect("Should have read");
+
+            assert_eq!(friend_list, create_friends(3));
+        }
+
+        #[test]
+        fn should_return_an_error_for_a_missing_file() {
+            let archive = MemoryArchive::default();
+            let result = read_friend_list(&archive, "/1/friendlist");
+            assert!(result.is_err());
+        }
+
+        #[test]
+        fn should_stop_at_the_end_of_the_file() {
+            let mut archive = MemoryArchive::default();
+            archive.files.insert(
+                "/1/friendlist".into(),
+                create_friend_list_file(&create_friends(3)),
+            );
+
+            let friend_list =
+                read_friend_list(&archive, "/1/friendlist").expect("Should have read");
+
+            assert_eq!(friend_list.len(), 3);
+        }
+
+        #[test]
+        fn should_stop_at_the_first_empty_slot() {
+            let friends = create_friends(3);
+            let mut archive = MemoryArchive::default();
+            archive.files.insert(
+                "/1/friendlist".into(),
+                create_friend_list_file(&[
+                    friends[0],
+                    friends[1],
+                    FriendEntry::default(),
+                    friends[2],
+                ]),
+            );
+
+            let friend_list =
+                read_friend_list(&archive, "/1/friendlist").expect("Should have read");
+
+            assert_eq!(friend_list, &friends[..2]);
+        }
+
+        #[test]
+        fn should_skip_duplicate_friends() {
+            let friends = create_friends(2);
+            let mut archive = MemoryArchive::default();
+            archive.files.insert(
+                "/1/friendlist".into(),
+                create_friend_list_file(&[friends[0], friends[1], friends[0]]),
+            );
+
+            let mut context = FriendServiceContext::new().unwrap();
+            context.friend_list =
+                read_friend_list(&archive, "/1/friendlist").expect("Should have read");
+
+            assert_eq!(
+                context.get_friend_keys(),
+                &[friends[0].friend_key, friends[1].friend_key]
+            );
+        }
     }
 }
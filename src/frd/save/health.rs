@@ -0,0 +1,304 @@
+use super::{
+    account::{AccountConfig, ACCOUNT_FILE_SIZE, ACCOUNT_MAGIC},
+    archive::SaveArchive,
+    friend_list::{FriendListHeader, FRIEND_LIST_HEADER_SIZE},
+    my_data::{MyData, MY_DATA_FILE_SIZE, MY_DATA_MAGIC},
+    path::{get_save_file_path, SaveFile},
+};
+use alloc::vec::Vec;
+use ctr::result::CtrResult;
+use no_std_io::Reader;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SaveFileStatus {
+    Ok,
+    WrongMagic,
+    /// The file was read and had the right magic, but its contents couldn't be parsed.
+    Corrupt,
+    IoError,
+    Missing,
+}
+
+/// The health of each save file, used by the boot path and diagnostics.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SaveHealthReport {
+    pub account: SaveFileStatus,
+    pub my_data: SaveFileStatus,
+    pub friend_list: SaveFileStatus,
+}
+
+impl SaveHealthReport {
+    pub fn is_healthy(&self) -> bool {
+        self.account == SaveFileStatus::Ok
+            && self.my_data == SaveFileStatus::Ok
+            && self.friend_list == SaveFileStatus::Ok
+    }
+}
+
+/// The magic is checked on its own, since the parsers return the same error
+/// for a wrong magic as they do for contents that fail validation.
+fn get_parse_status<T>(
+    raw_data: &[u8],
+    magic: u64,
+    parse: impl FnOnce(&[u8]) -> CtrResult<T>,
+) -> SaveFileStatus {
+    match raw_data.read_le::<u64>(0) {
+        Ok(file_magic) if file_magic != magic => SaveFileStatus::WrongMagic,
+        Ok(_) => match parse(raw_data) {
+            Ok(_) => SaveFileStatus::Ok,
+            Err(_) => SaveFileStatus::Corrupt,
+        },
+        Err(_) => SaveFileStatus::Corrupt,
+    }
+}
+
+/// Reports the status of an account file given the result of reading it.
+pub fn get_account_status(read_result: Result<Vec<u8>, SaveFileStatus>) -> SaveFileStatus {
+    match read_result {
+        // A truncated file is corrupt, whatever its first bytes are
+        Ok(raw_data) if raw_data.len() < ACCOUNT_FILE_SIZE => SaveFileStatus::Corrupt,
+        Ok(raw_data) => {
+            get_parse_status(&raw_data, ACCOUNT_MAGIC, AccountConfig::try_from_le_bytes)
+        }
+        Err(status) => status,
+    }
+}
+
+/// Reports the status of a mydata file given the result of reading it.
+pub fn get_my_data_status(read_result: Result<Vec<u8>, SaveFileStatus>) -> SaveFileStatus {
+    match read_result {
+        Ok(raw_data) => get_parse_status(&raw_data, MY_DATA_MAGIC, MyData::try_from_le_bytes),
+        Err(status) => status,
+    }
+}
+
+/// Reports the status of a friend list file given the result of reading its header.
+pub fn get_friend_list_status(read_result: Result<Vec<u8>, SaveFileStatus>) -> SaveFileStatus {
+    match read_result {
+        Ok(raw_header) if raw_header.len() < FRIEND_LIST_HEADER_SIZE => SaveFileStatus::Corrupt,
+        Ok(raw_header) => match raw_header.read_le::<FriendListHeader>(0) {
+            Ok(header) if header.validate().is_ok() => SaveFileStatus::Ok,
+            Ok(_) => SaveFileStatus::WrongMagic,
+            Err(_) => SaveFileStatus::Corrupt,
+        },
+        Err(status) => status,
+    }
+}
+
+fn read_save_file<A: SaveArchive>(
+    archive: &A,
+    local_account_id: u32,
+    save_file: SaveFile,
+    size: usize,
+) -> Result<Vec<u8>, SaveFileStatus> {
+    let path =
+        get_save_file_path(local_account_id, save_file).map_err(|_| SaveFileStatus::IoError)?;
+
+    if !archive.exists(&path) {
+        return Err(SaveFileStatus::Missing);
+    }
+
+    archive
+        .read(&path, 0, size)
+        .map_err(|_| SaveFileStatus::IoError)
+}
+
+/// Checks each save file of a local account without loading any of them.
+/// This takes the archive instead of being a FriendServiceContext method,
+/// since boot checks the saves before the context exists.
+pub fn validate_saves<A: SaveArchive>(archive: &A, local_account_id: u32) -> SaveHealthReport {
+    SaveHealthReport {
+        account: get_account_status(read_save_file(
+            archive,
+            local_account_id,
+            SaveFile::Account,
+            ACCOUNT_FILE_SIZE,
+        )),
+        my_data: get_my_data_status(read_save_file(
+            archive,
+            local_account_id,
+            SaveFile::MyData,
+            MY_DATA_FILE_SIZE,
+        )),
+        friend_list: get_friend_list_status(read_save_file(
+            archive,
+            local_account_id,
+            SaveFile::FriendList,
+            FRIEND_LIST_HEADER_SIZE,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frd::save::friend_list::FRIEND_LIST_MAGIC;
+    use alloc::vec;
+
+    fn create_account_bytes() -> Vec<u8> {
+        let mut bytes = vec![0; ACCOUNT_FILE_SIZE];
+        bytes[..8].copy_from_slice(&ACCOUNT_MAGIC.to_le_bytes());
+        bytes
+    }
+
+    fn create_friend_list_header_bytes() -> Vec<u8> {
+        let mut bytes = vec![0; FRIEND_LIST_HEADER_SIZE];
+        bytes[..8].copy_from_slice(&FRIEND_LIST_MAGIC.to_le_bytes());
+        bytes
+    }
+
+    fn create_my_data_bytes() -> Vec<u8> {
+        let mut bytes = vec![0; MY_DATA_FILE_SIZE];
+        bytes[..8].copy_from_slice(&MY_DATA_MAGIC.to_le_bytes());
+        bytes
+    }
+
+    mod get_account_status {
+        use super::*;
+
+        #[test]
+        fn should_return_ok_for_a_valid_account() {
+            let result = get_account_status(Ok(create_account_bytes()));
+            assert_eq!(result, SaveFileStatus::Ok);
+        }
+
+        #[test]
+        fn should_return_wrong_magic_for_an_invalid_header() {
+            let mut bytes = create_account_bytes();
+            bytes[0] = 0;
+            let result = get_account_status(Ok(bytes));
+            assert_eq!(result, SaveFileStatus::WrongMagic);
+        }
+
+        #[test]
+        fn should_return_corrupt_for_an_unknown_server_type() {
+            let mut bytes = create_account_bytes();
+            // The first server type
+            bytes[85] = 0xff;
+
+            let result = get_account_status(Ok(bytes));
+            assert_eq!(result, SaveFileStatus::Corrupt);
+        }
+
+        #[test]
+        fn should_return_corrupt_for_a_short_file() {
+            let result = get_account_status(Ok(vec![0; 10]));
+            assert_eq!(result, SaveFileStatus::Corrupt);
+        }
+
+        #[test]
+        fn should_pass_through_read_errors() {
+            let result = get_account_status(Err(SaveFileStatus::Missing));
+            assert_eq!(result, SaveFileStatus::Missing);
+        }
+    }
+
+    mod get_my_data_status {
+        use super::*;
+
+        #[test]
+        fn should_return_ok_for_valid_my_data() {
+            let result = get_my_data_status(Ok(create_my_data_bytes()));
+            assert_eq!(result, SaveFileStatus::Ok);
+        }
+
+        #[test]
+        fn should_return_wrong_magic_when_given_an_account_file() {
+            let mut bytes = create_account_bytes();
+            bytes.resize(MY_DATA_FILE_SIZE, 0);
+            let result = get_my_data_status(Ok(bytes));
+            assert_eq!(result, SaveFileStatus::WrongMagic);
+        }
+
+        #[test]
+        fn should_return_corrupt_for_an_out_of_range_profile() {
+            let mut bytes = create_my_data_bytes();
+            // The profile region
+            bytes[88] = 0xff;
+
+            let result = get_my_data_status(Ok(bytes));
+            assert_eq!(result, SaveFileStatus::Corrupt);
+        }
+    }
+
+    mod get_friend_list_status {
+        use super::*;
+
+        #[test]
+        fn should_return_ok_for_a_valid_header() {
+            let status = get_friend_list_status(Ok(create_friend_list_header_bytes()));
+            assert_eq!(status, SaveFileStatus::Ok);
+        }
+
+        #[test]
+        fn should_return_wrong_magic_for_an_unknown_version() {
+            let mut bytes = create_friend_list_header_bytes();
+            bytes[4..8].copy_from_slice(&0x20201021u32.to_le_bytes());
+
+            let status = get_friend_list_status(Ok(bytes));
+            assert_eq!(status, SaveFileStatus::WrongMagic);
+        }
+    }
+
+    mod validate_saves {
+        use super::*;
+        use crate::frd::save::archive::MemoryArchive;
+
+        fn insert_save_file(archive: &mut MemoryArchive, save_file: SaveFile, bytes: Vec<u8>) {
+            let path = get_save_file_path(1, save_file).unwrap();
+            archive.files.insert(path, bytes);
+        }
+
+        #[test]
+        fn should_report_each_file_independently() {
+            let mut corrupt_my_data = create_my_data_bytes();
+            corrupt_my_data[4] = 0xff;
+
+            let mut archive = MemoryArchive::default();
+            insert_save_file(&mut archive, SaveFile::Account, create_account_bytes());
+            insert_save_file(&mut archive, SaveFile::MyData, corrupt_my_data);
+
+            let report = validate_saves(&archive, 1);
+
+            assert_eq!(
+                report,
+                SaveHealthReport {
+                    account: SaveFileStatus::Ok,
+                    my_data: SaveFileStatus::WrongMagic,
+                    friend_list: SaveFileStatus::Missing,
+                }
+            );
+            assert!(!report.is_healthy());
+        }
+
+        #[test]
+        fn should_be_healthy_when_all_files_are_ok() {
+            let mut archive = MemoryArchive::default();
+            insert_save_file(&mut archive, SaveFile::Account, create_account_bytes());
+            insert_save_file(&mut archive, SaveFile::MyData, create_my_data_bytes());
+            insert_save_file(
+                &mut archive,
+                SaveFile::FriendList,
+                create_friend_list_header_bytes(),
+            );
+
+            let report = validate_saves(&archive, 1);
+
+            assert!(report.is_healthy());
+        }
+
+        #[test]
+        fn should_report_an_io_error_for_an_invalid_account_id() {
+            let report = validate_saves(&MemoryArchive::default(), 0);
+
+            assert_eq!(
+                report,
+                SaveHealthReport {
+                    account: SaveFileStatus::IoError,
+                    my_data: SaveFileStatus::IoError,
+                    friend_list: SaveFileStatus::IoError,
+                }
+            );
+        }
+    }
+}
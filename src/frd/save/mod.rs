@@ -1,3 +1,7 @@
 pub mod account;
+pub mod archive;
 pub mod friend_list;
+pub mod health;
 pub mod my_data;
+pub mod path;
+mod string;
@@ -1,8 +1,60 @@
-use crate::frd::result::FrdErrorCode;
-use alloc::string::String;
-use core::convert::TryInto;
-use ctr::frd::{FriendProfile, GameKey, Mii};
-use ctr::{result::CtrResult, utils::convert::bytes_to_utf16le_string};
+use super::string::{read_utf16le_field, write_utf16le_field};
+use crate::{
+    frd::{result::FrdErrorCode, utils::is_valid_mii},
+    log,
+};
+use alloc::{string::String, vec, vec::Vec};
+use ctr::frd::{FriendProfile, GameKey, Mii, ScreenName};
+use ctr::result::CtrResult;
+use no_std_io::{EndianRead, EndianWrite, Reader, Writer};
+
+// Legal profile ranges:
+// - region: 0 (JPN) through 6 (TWN)
+// - country: 0 through 186, the last code in the system country list
+// - area: a country specific subdivision, so any value is accepted
+// - language: 0 (JP) through 11 (TW)
+// - platform: 2 for the 3ds, 1 for the Wii U, and 0 for a profile that was never filled in
+const MAX_PROFILE_REGION: u8 = 6;
+const MAX_PROFILE_COUNTRY: u8 = 186;
+const MAX_PROFILE_LANGUAGE: u8 = 11;
+const MAX_PROFILE_PLATFORM: u8 = 2;
+
+pub fn validate_profile(profile: &FriendProfile) -> CtrResult<()> {
+    if profile.region > MAX_PROFILE_REGION
+        || profile.country > MAX_PROFILE_COUNTRY
+        || profile.language > MAX_PROFILE_LANGUAGE
+        || profile.platform > MAX_PROFILE_PLATFORM
+    {
+        return Err(FrdErrorCode::InvalidFriendListOrMyDataSaveFile.into());
+    }
+
+    Ok(())
+}
+
+/// Marks any field of a partial profile update as unchanged.
+/// 0 can't be used, since it's a legal value for every field.
+/// 0xff is past the legal range of every field but the area, so an update can't set the area to 0xff.
+pub const PROFILE_FIELD_UNCHANGED: u8 = 0xff;
+
+/// Applies a partial profile update, keeping any field set to [PROFILE_FIELD_UNCHANGED].
+pub fn merge_profile_update(profile: &FriendProfile, update: &FriendProfile) -> FriendProfile {
+    let merge_field = |field: u8, update_field: u8| {
+        if update_field == PROFILE_FIELD_UNCHANGED {
+            field
+        } else {
+            update_field
+        }
+    };
+
+    FriendProfile {
+        region: merge_field(profile.region, update.region),
+        country: merge_field(profile.country, update.country),
+        area: merge_field(profile.area, update.area),
+        language: merge_field(profile.language, update.language),
+        platform: merge_field(profile.platform, update.platform),
+        padding: profile.padding,
+    }
+}
 
 pub struct MyData {
     pub my_nc_principal_id: u32,
@@ -19,46 +71,469 @@ pub struct MyData {
     pub mii: Mii,
 }
 
+/// The 11 word GetMyScreenName output.
+/// Only the screen name's place is known. Putting the language at byte 24 hasn't been
+/// confirmed against the official sysmodule, and the words after it are zeroed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EndianRead, EndianWrite)]
+pub struct MyScreenName {
+    pub screen_name: ScreenName,
+    pub padding: u16,
+    /// The profile language, so clients can pick the right font for the name.
+    pub language: u32,
+    pub reserved: [u32; 4],
+}
+
+/// Screen names hold up to 10 UTF-16 characters.
+/// Both the save field and GetMyScreenName output have one more short for the null terminator.
+const SCREEN_NAME_MAX_LENGTH: usize = 10;
+const SCREEN_NAME_FIELD_SIZE: usize = (SCREEN_NAME_MAX_LENGTH + 1) * 2;
+
+// Bits of the packed preference word
+const PREFERENCE_PUBLIC_MODE_BIT: u32 = 1 << 0;
+const PREFERENCE_SHOW_GAME_MODE_BIT: u32 = 1 << 1;
+const PREFERENCE_SHOW_PLAYED_GAME_BIT: u32 = 1 << 2;
+
+pub const MY_DATA_MAGIC: u64 = 0x20101021444d5046;
+
+pub const MY_DATA_FILE_SIZE: usize = 288;
+
+/// The raw 288 byte layout of the mydata save file.
+#[derive(EndianRead, EndianWrite)]
+struct RawMyData {
+    magic: u64,
+    unk1: [u8; 8],
+    my_nc_principal_id: u32,
+    unk2: u32,
+    changed_bit_flags: u32,
+    is_public_mode: u8,
+    is_show_game_mode: u8,
+    is_show_played_game: u8,
+    unk3: u8,
+    my_favorite_game: GameKey,
+    personal_comment: [u8; 34],
+    unk4: [u8; 6],
+    profile: FriendProfile,
+    unk5: [u8; 8],
+    mac_address: [u8; 26],
+    console_serial_number: [u8; 32],
+    screen_name: [u8; SCREEN_NAME_FIELD_SIZE],
+    unk6: [u8; 3],
+    mii: Mii,
+    unk7: [u8; 5],
+}
+
 impl MyData {
-    // This explicitly mentions the endianness instead of From<[u8; 288]>
-    pub fn try_from_le_bytes(raw_data: [u8; 288]) -> CtrResult<Self> {
-        let header_bytes = raw_data[..8].try_into().unwrap();
+    pub fn get_my_screen_name(&self) -> MyScreenName {
+        let mut screen_name: [u16; SCREEN_NAME_MAX_LENGTH + 1] = [0; SCREEN_NAME_MAX_LENGTH + 1];
+        self.screen_name
+            .encode_utf16()
+            .take(SCREEN_NAME_MAX_LENGTH)
+            .enumerate()
+            .for_each(|(index, short)| {
+                screen_name[index] = short;
+            });
+
+        MyScreenName {
+            screen_name: ScreenName::new(screen_name),
+            padding: 0,
+            language: self.profile.language as u32,
+            reserved: [0; 4],
+        }
+    }
+
+    /// Packs the preferences into one word, for clients that expect a bitfield
+    /// instead of the three words GetMyPreference returns.
+    pub fn get_packed_preference(&self) -> u32 {
+        let mut packed_preference = 0;
+
+        if self.is_public_mode {
+            packed_preference |= PREFERENCE_PUBLIC_MODE_BIT;
+        }
+
+        if self.is_show_game_mode {
+            packed_preference |= PREFERENCE_SHOW_GAME_MODE_BIT;
+        }
+
+        if self.is_show_played_game {
+            packed_preference |= PREFERENCE_SHOW_PLAYED_GAME_BIT;
+        }
+
+        packed_preference
+    }
+
+    /// Creates the mydata save file bytes.
+    /// Fields this doesn't know about are kept from the original file, or zeroed if there isn't one.
+    pub fn to_le_bytes(&self, original_raw_data: &[u8]) -> CtrResult<Vec<u8>> {
+        let mut raw_data = vec![0; MY_DATA_FILE_SIZE];
+        if original_raw_data.len() == MY_DATA_FILE_SIZE {
+            raw_data.copy_from_slice(original_raw_data);
+        }
+
+        let mut raw_my_data: RawMyData = raw_data.read_le(0)?;
+        raw_my_data.magic = MY_DATA_MAGIC;
+        raw_my_data.my_nc_principal_id = self.my_nc_principal_id;
+        raw_my_data.changed_bit_flags = self.changed_bit_flags;
+        raw_my_data.is_public_mode = self.is_public_mode as u8;
+        raw_my_data.is_show_game_mode = self.is_show_game_mode as u8;
+        raw_my_data.is_show_played_game = self.is_show_played_game as u8;
+        raw_my_data.my_favorite_game = self.my_favorite_game;
+        write_utf16le_field(&self.personal_comment, &mut raw_my_data.personal_comment);
+        raw_my_data.profile = self.profile;
+        write_utf16le_field(&self.mac_address, &mut raw_my_data.mac_address);
+        write_utf16le_field(
+            &self.console_serial_number,
+            &mut raw_my_data.console_serial_number,
+        );
+        write_utf16le_field(&self.screen_name, &mut raw_my_data.screen_name);
+        raw_my_data.mii = self.mii;
 
-        if u64::from_le_bytes(header_bytes) != 0x20101021444d5046 {
+        raw_data.checked_write_le(0, &raw_my_data);
+        Ok(raw_data)
+    }
+
+    // This explicitly mentions the endianness instead of TryFrom<&[u8]>
+    pub fn try_from_le_bytes(raw_data: &[u8]) -> CtrResult<Self> {
+        let magic: u64 = raw_data.read_le(0)?;
+
+        if magic != MY_DATA_MAGIC {
             return Err(FrdErrorCode::InvalidFriendListOrMyDataSaveFile.into());
         }
 
-        let my_nc_principal_id_bytes = raw_data[16..20].try_into().unwrap();
-        let changed_bit_flags_bytes = raw_data[24..28].try_into().unwrap();
+        let raw_my_data: RawMyData = raw_data.read_le(0)?;
+        validate_profile(&raw_my_data.profile)?;
 
-        let title_id_bytes = raw_data[32..40].try_into().unwrap();
-        let title_version_bytes = raw_data[40..44].try_into().unwrap();
-        let game_key_unk_bytes = raw_data[44..48].try_into().unwrap();
+        // A bad Mii shouldn't lock the user out of their account, so fall back to a blank one
+        let mii = if is_valid_mii(&raw_my_data.mii) {
+            raw_my_data.mii
+        } else {
+            log::debug("Mydata has an invalid mii, using a default mii");
+            Mii::default()
+        };
 
         Ok(Self {
-            my_nc_principal_id: u32::from_le_bytes(my_nc_principal_id_bytes),
-            changed_bit_flags: u32::from_le_bytes(changed_bit_flags_bytes),
-            is_public_mode: raw_data[28] != 0,
-            is_show_game_mode: raw_data[29] != 0,
-            is_show_played_game: raw_data[30] != 0,
-            my_favorite_game: GameKey {
-                title_id: u64::from_le_bytes(title_id_bytes),
-                version: u32::from_le_bytes(title_version_bytes),
-                unk: u32::from_le_bytes(game_key_unk_bytes),
-            },
-            personal_comment: bytes_to_utf16le_string(&raw_data[48..82])?,
-            profile: FriendProfile {
-                region: raw_data[88],
-                country: raw_data[89],
-                area: raw_data[90],
-                language: raw_data[91],
-                platform: raw_data[92],
-                padding: raw_data[93..96].try_into().unwrap(),
-            },
-            mac_address: bytes_to_utf16le_string(&raw_data[104..130])?,
-            console_serial_number: bytes_to_utf16le_string(&raw_data[130..162])?,
-            screen_name: bytes_to_utf16le_string(&raw_data[162..184])?,
-            mii: Mii::new(raw_data[187..283].try_into().unwrap()),
+            my_nc_principal_id: raw_my_data.my_nc_principal_id,
+            changed_bit_flags: raw_my_data.changed_bit_flags,
+            is_public_mode: raw_my_data.is_public_mode != 0,
+            is_show_game_mode: raw_my_data.is_show_game_mode != 0,
+            is_show_played_game: raw_my_data.is_show_played_game != 0,
+            my_favorite_game: raw_my_data.my_favorite_game,
+            personal_comment: read_utf16le_field(
+                &raw_my_data.personal_comment,
+                "mydata.personal_comment",
+            ),
+            profile: raw_my_data.profile,
+            mac_address: read_utf16le_field(&raw_my_data.mac_address, "mydata.mac_address"),
+            console_serial_number: read_utf16le_field(
+                &raw_my_data.console_serial_number,
+                "mydata.console_serial_number",
+            ),
+            screen_name: read_utf16le_field(&raw_my_data.screen_name, "mydata.screen_name"),
+            mii,
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frd::utils::{create_mii_bytes, MII_DATA_SIZE};
+
+    const MII_OFFSET: usize = 187;
+
+    fn create_my_data_bytes() -> [u8; 288] {
+        let mut bytes = [0; 288];
+        bytes[..8].copy_from_slice(&0x20101021444d5046u64.to_le_bytes());
+        bytes[88..93].copy_from_slice(&[1, 49, 2, 1, 2]);
+        bytes
+    }
+
+    mod try_from_le_bytes {
+        use super::*;
+
+        #[test]
+        fn should_parse_a_valid_profile() {
+            let my_data = MyData::try_from_le_bytes(&create_my_data_bytes())
+                .expect("Should have parsed my data");
+
+            assert_eq!(my_data.profile.region, 1);
+            assert_eq!(my_data.profile.country, 49);
+            assert_eq!(my_data.profile.area, 2);
+            assert_eq!(my_data.profile.language, 1);
+            assert_eq!(my_data.profile.platform, 2);
+            assert_eq!(my_data.screen_name, "");
+        }
+
+        #[test]
+        fn should_return_an_error_for_an_out_of_range_platform() {
+            let mut bytes = create_my_data_bytes();
+            bytes[92] = 0xff;
+
+            let result_code = MyData::try_from_le_bytes(&bytes)
+                .err()
+                .expect("Expected error code");
+            assert_eq!(
+                result_code,
+                FrdErrorCode::InvalidFriendListOrMyDataSaveFile.into_result_code()
+            );
+        }
+
+        #[test]
+        fn should_return_an_error_for_a_truncated_buffer() {
+            let bytes = create_my_data_bytes();
+            let result = MyData::try_from_le_bytes(&bytes[..200]);
+            assert!(result.is_err());
+        }
+
+        #[test]
+        fn should_return_an_error_for_a_buffer_shorter_than_the_magic() {
+            let result = MyData::try_from_le_bytes(&[0x46, 0x50]);
+            assert!(result.is_err());
+        }
+
+        #[test]
+        fn should_return_an_error_for_an_invalid_magic() {
+            let mut bytes = create_my_data_bytes();
+            bytes[0] = 0;
+
+            let result_code = MyData::try_from_le_bytes(&bytes)
+                .err()
+                .expect("Expected error code");
+            assert_eq!(
+                result_code,
+                FrdErrorCode::InvalidFriendListOrMyDataSaveFile.into_result_code()
+            );
+        }
+
+        #[test]
+        fn should_load_with_an_empty_screen_name_when_it_is_corrupt() {
+            let mut bytes = create_my_data_bytes();
+            bytes[104..106].copy_from_slice(&[0x41, 0x00]);
+            bytes[162..166].copy_from_slice(&[0x4d, 0x00, 0x00, 0xd8]);
+
+            let my_data = MyData::try_from_le_bytes(&bytes).expect("Should have parsed my data");
+
+            assert_eq!(my_data.screen_name, "");
+            assert_eq!(my_data.mac_address, "A");
+        }
+
+        #[test]
+        fn should_keep_a_valid_mii() {
+            let mut bytes = create_my_data_bytes();
+            bytes[MII_OFFSET..MII_OFFSET + MII_DATA_SIZE].copy_from_slice(&create_mii_bytes());
+
+            let my_data = MyData::try_from_le_bytes(&bytes).expect("Should have parsed my data");
+            assert_eq!(my_data.mii, Mii::new(create_mii_bytes()));
+        }
+
+        #[test]
+        fn should_replace_a_corrupt_mii_with_a_default_mii() {
+            let mut mii_bytes = create_mii_bytes();
+            mii_bytes[0x10] ^= 0xff;
+            let mut bytes = create_my_data_bytes();
+            bytes[MII_OFFSET..MII_OFFSET + MII_DATA_SIZE].copy_from_slice(&mii_bytes);
+
+            let my_data = MyData::try_from_le_bytes(&bytes).expect("Should have parsed my data");
+            assert_eq!(my_data.mii, Mii::default());
+        }
+
+        #[test]
+        fn should_return_an_error_for_an_out_of_range_region() {
+            let mut bytes = create_my_data_bytes();
+            bytes[88] = 7;
+
+            let result = MyData::try_from_le_bytes(&bytes);
+            assert!(result.is_err());
+        }
+    }
+
+    mod to_le_bytes {
+        use super::*;
+
+        #[test]
+        fn should_round_trip_through_try_from_le_bytes() {
+            let mut my_data = MyData::try_from_le_bytes(&create_my_data_bytes()).unwrap();
+            my_data.is_public_mode = true;
+            my_data.personal_comment = "Hello".into();
+            my_data.screen_name = "Mock".into();
+
+            let bytes = my_data.to_le_bytes(&create_my_data_bytes()).unwrap();
+            let result = MyData::try_from_le_bytes(&bytes).unwrap();
+
+            assert!(result.is_public_mode);
+            assert_eq!(result.personal_comment, "Hello");
+            assert_eq!(result.screen_name, "Mock");
+            assert_eq!(result.profile, my_data.profile);
+        }
+
+        #[test]
+        fn should_keep_unknown_fields_from_the_original_file() {
+            let mut original_bytes = create_my_data_bytes();
+            original_bytes[8] = 0xaa;
+            let my_data = MyData::try_from_le_bytes(&original_bytes).unwrap();
+
+            let bytes = my_data.to_le_bytes(&original_bytes).unwrap();
+
+            assert_eq!(bytes, original_bytes);
+        }
+
+        #[test]
+        fn should_create_a_file_without_an_original() {
+            let my_data = MyData::try_from_le_bytes(&create_my_data_bytes()).unwrap();
+
+            let bytes = my_data.to_le_bytes(&[]).unwrap();
+
+            assert_eq!(bytes, create_my_data_bytes());
+        }
+    }
+
+    mod get_my_screen_name {
+        use super::*;
+        use alloc::vec;
+        use no_std_io::Writer;
+
+        #[test]
+        fn should_include_the_profile_language() {
+            let mut bytes = create_my_data_bytes();
+            bytes[162..166].copy_from_slice(&[0x4d, 0x00, 0x6f, 0x00]);
+            let my_data = MyData::try_from_le_bytes(&bytes).unwrap();
+
+            let my_screen_name = my_data.get_my_screen_name();
+            let mut output = vec![];
+            output.checked_write_le(0, &my_screen_name);
+
+            assert_eq!(output.len(), 11 * 4);
+            assert_eq!(output[..6], [0x4d, 0x00, 0x6f, 0x00, 0x00, 0x00]);
+            assert_eq!(
+                output[24..28],
+                (my_data.profile.language as u32).to_le_bytes()
+            );
+        }
+
+        #[test]
+        fn should_keep_every_character_of_a_full_length_name() {
+            let mut bytes = create_my_data_bytes();
+            for (index, char) in "ABCDEFGHIJ".encode_utf16().enumerate() {
+                let offset = 162 + (index * 2);
+                bytes[offset..offset + 2].copy_from_slice(&char.to_le_bytes());
+            }
+            let my_data = MyData::try_from_le_bytes(&bytes).unwrap();
+
+            let my_screen_name = my_data.get_my_screen_name();
+            let mut output = vec![];
+            output.checked_write_le(0, &my_screen_name);
+
+            assert_eq!(my_data.screen_name, "ABCDEFGHIJ");
+            assert_eq!(output[..22], bytes[162..184]);
+        }
+    }
+
+    mod get_packed_preference {
+        use super::*;
+
+        #[test]
+        fn should_pack_each_preference_into_its_own_bit() {
+            let mut my_data = MyData::try_from_le_bytes(&create_my_data_bytes()).unwrap();
+            assert_eq!(my_data.get_packed_preference(), 0);
+
+            my_data.is_public_mode = true;
+            assert_eq!(my_data.get_packed_preference(), 0b001);
+
+            my_data.is_public_mode = false;
+            my_data.is_show_game_mode = true;
+            assert_eq!(my_data.get_packed_preference(), 0b010);
+
+            my_data.is_show_game_mode = false;
+            my_data.is_show_played_game = true;
+            assert_eq!(my_data.get_packed_preference(), 0b100);
+
+            my_data.is_public_mode = true;
+            my_data.is_show_game_mode = true;
+            assert_eq!(my_data.get_packed_preference(), 0b111);
+        }
+    }
+
+    mod merge_profile_update {
+        use super::*;
+
+        fn create_profile() -> FriendProfile {
+            FriendProfile {
+                region: 1,
+                country: 49,
+                area: 2,
+                language: 1,
+                platform: 2,
+                padding: [0; 3],
+            }
+        }
+
+        #[test]
+        fn should_only_overwrite_changed_fields() {
+            let update = FriendProfile {
+                region: PROFILE_FIELD_UNCHANGED,
+                country: PROFILE_FIELD_UNCHANGED,
+                area: 5,
+                language: 0,
+                platform: PROFILE_FIELD_UNCHANGED,
+                padding: [0; 3],
+            };
+
+            let result = merge_profile_update(&create_profile(), &update);
+
+            assert_eq!(result.region, 1);
+            assert_eq!(result.country, 49);
+            assert_eq!(result.area, 5);
+            assert_eq!(result.language, 0);
+            assert_eq!(result.platform, 2);
+        }
+
+        #[test]
+        fn should_overwrite_fields_set_to_0() {
+            let update = FriendProfile {
+                region: 0,
+                country: 0,
+                area: 0,
+                language: 0,
+                platform: 0,
+                padding: [0; 3],
+            };
+
+            let result = merge_profile_update(&create_profile(), &update);
+
+            assert_eq!(result.region, 0);
+            assert_eq!(result.country, 0);
+            assert_eq!(result.area, 0);
+            assert_eq!(result.language, 0);
+            assert_eq!(result.platform, 0);
+        }
+    }
+
+    mod validate_profile {
+        use super::*;
+
+        #[test]
+        fn should_accept_the_upper_bounds() {
+            let profile = FriendProfile {
+                region: 6,
+                country: 186,
+                area: 0xff,
+                language: 11,
+                platform: 2,
+                padding: [0; 3],
+            };
+            assert!(validate_profile(&profile).is_ok());
+        }
+
+        #[test]
+        fn should_reject_an_out_of_range_language() {
+            let profile = FriendProfile {
+                region: 0,
+                country: 0,
+                area: 0,
+                language: 12,
+                platform: 2,
+                padding: [0; 3],
+            };
+            assert!(validate_profile(&profile).is_err());
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use crate::frd::result::FrdErrorCode;
+use alloc::{format, string::String};
+
+const MIN_LOCAL_ACCOUNT_ID: u32 = 1;
+const MAX_LOCAL_ACCOUNT_ID: u32 = 255;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SaveFile {
+    Account,
+    MyData,
+    FriendList,
+}
+
+impl SaveFile {
+    fn get_file_name(self) -> &'static str {
+        match self {
+            Self::Account => "account",
+            Self::MyData => "mydata",
+            Self::FriendList => "friendlist",
+        }
+    }
+}
+
+/// Builds the path of a save file for a local account.
+///
+/// The id is checked first so only a known good ASCII path is ever opened.
+pub fn get_save_file_path(
+    local_account_id: u32,
+    save_file: SaveFile,
+) -> Result<String, FrdErrorCode> {
+    if !(MIN_LOCAL_ACCOUNT_ID..=MAX_LOCAL_ACCOUNT_ID).contains(&local_account_id) {
+        return Err(FrdErrorCode::InvalidArguments);
+    }
+
+    Ok(format!(
+        "/{}/{}",
+        local_account_id,
+        save_file.get_file_name()
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    mod get_save_file_path {
+        use super::*;
+
+        #[test]
+        fn should_build_the_path_for_each_file() {
+            assert_eq!(
+                get_save_file_path(1, SaveFile::Account),
+                Ok("/1/account".into())
+            );
+            assert_eq!(
+                get_save_file_path(1, SaveFile::MyData),
+                Ok("/1/mydata".into())
+            );
+            assert_eq!(
+                get_save_file_path(255, SaveFile::FriendList),
+                Ok("/255/friendlist".into())
+            );
+        }
+
+        #[test]
+        fn should_reject_account_id_0() {
+            let result = get_save_file_path(0, SaveFile::Account);
+            assert_eq!(result, Err(FrdErrorCode::InvalidArguments));
+        }
+
+        #[test]
+        fn should_reject_an_account_id_above_255() {
+            let result = get_save_file_path(256, SaveFile::Account);
+            assert_eq!(result, Err(FrdErrorCode::InvalidArguments));
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use crate::log;
+use alloc::{format, string::String};
+use ctr::utils::convert::bytes_to_utf16le_string;
+
+/// Decodes a UTF-16 save field, falling back to an empty string so one bad field
+/// doesn't stop the whole save from loading.
+pub fn read_utf16le_field(raw_field: &[u8], field_name: &str) -> String {
+    bytes_to_utf16le_string(raw_field).unwrap_or_else(|_| {
+        log::debug(&format!(
+            "Invalid UTF-16 in {}, using an empty string",
+            field_name
+        ));
+        String::new()
+    })
+}
+
+/// Encodes a string into a UTF-16 save field, truncating it so the field stays null terminated.
+pub fn write_utf16le_field(value: &str, raw_field: &mut [u8]) {
+    raw_field.fill(0);
+
+    let max_char_count = raw_field.len().saturating_sub(2) / 2;
+    for (index, char) in value.encode_utf16().take(max_char_count).enumerate() {
+        raw_field[index * 2..(index * 2) + 2].copy_from_slice(&char.to_le_bytes());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    mod read_utf16le_field {
+        use super::*;
+
+        #[test]
+        fn should_decode_a_valid_field() {
+            let result = read_utf16le_field(&[0x4d, 0x00, 0x6f, 0x00, 0x00, 0x00], "test");
+            assert_eq!(result, "Mo");
+        }
+
+        #[test]
+        fn should_return_an_empty_string_for_an_unpaired_surrogate() {
+            let result = read_utf16le_field(&[0x4d, 0x00, 0x00, 0xd8, 0x00, 0x00], "test");
+            assert_eq!(result, "");
+        }
+    }
+
+    mod write_utf16le_field {
+        use super::*;
+
+        #[test]
+        fn should_encode_the_string() {
+            let mut raw_field = [0xff; 6];
+            write_utf16le_field("Mo", &mut raw_field);
+            assert_eq!(raw_field, [0x4d, 0x00, 0x6f, 0x00, 0x00, 0x00]);
+        }
+
+        #[test]
+        fn should_leave_room_for_the_null_terminator() {
+            let mut raw_field = [0xff; 6];
+            write_utf16le_field("Mock", &mut raw_field);
+            assert_eq!(raw_field, [0x4d, 0x00, 0x6f, 0x00, 0x00, 0x00]);
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use crate::frd::result::FrdErrorCode;
+use alloc::vec::Vec;
+use core::mem;
+use ctr::ipc::{PermissionBuffer, StaticBuffer};
+use no_std_io::{EndianRead, Reader};
+
+/// Reads each whole `T` in a client buffer.
+///
+/// A trailing partial element is ignored, so a buffer that isn't a multiple
+/// of the item size is never read past its end.
+pub fn read_buffer_items<T: EndianRead>(bytes: &[u8]) -> impl Iterator<Item = T> + '_ {
+    bytes
+        .chunks_exact(mem::size_of::<T>())
+        .filter_map(|chunk| chunk.read_le(0).ok())
+}
+
+/// Reads each whole `T` in a static buffer sent by a client.
+pub fn read_static_buffer_items<T: EndianRead>(buffer: &StaticBuffer) -> Vec<T> {
+    // Bytes are read one at a time, so this stays within the buffer's length
+    let bytes: Vec<u8> = unsafe { buffer.iter::<u8>() }.collect();
+    read_buffer_items(&bytes).collect()
+}
+
+/// Counts the whole `T` items in a static buffer sent by a client,
+/// for types that can only be read with the buffer's unchecked iterator.
+pub fn get_static_buffer_item_count<T>(buffer: &StaticBuffer) -> usize {
+    let byte_count = unsafe { buffer.iter::<u8>() }.count();
+    byte_count / mem::size_of::<T>()
+}
+
+/// Checks a buffer mapped by a client isn't null before it's written to.
+///
+/// Static buffers don't need this, since the kernel copies them into buffers the sysmodule owns.
+pub fn validate_permission_buffer(buffer: &PermissionBuffer) -> Result<(), FrdErrorCode> {
+    if buffer.ptr().is_null() {
+        return Err(FrdErrorCode::InvalidPointer);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ctr::frd::FriendKey;
+
+    mod read_buffer_items {
+        use super::*;
+
+        #[test]
+        fn should_read_each_item() {
+            let mut bytes = [0u8; 32];
+            bytes[0..4].copy_from_slice(&0x11223344u32.to_le_bytes());
+            bytes[16..20].copy_from_slice(&0x55667788u32.to_le_bytes());
+
+            let result: Vec<FriendKey> = read_buffer_items(&bytes).collect();
+
+            assert_eq!(result.len(), 2);
+            assert_eq!(result[0].principal_id, 0x11223344);
+            assert_eq!(result[1].principal_id, 0x55667788);
+        }
+
+        #[test]
+        fn should_ignore_a_trailing_partial_item() {
+            let mut bytes = [0u8; 20];
+            bytes[0..4].copy_from_slice(&0x11223344u32.to_le_bytes());
+            bytes[16..20].copy_from_slice(&0x55667788u32.to_le_bytes());
+
+            let result: Vec<FriendKey> = read_buffer_items(&bytes).collect();
+
+            assert_eq!(result.len(), 1);
+            assert_eq!(result[0].principal_id, 0x11223344);
+        }
+
+        #[test]
+        fn should_read_nothing_from_an_empty_buffer() {
+            let result: Vec<FriendKey> = read_buffer_items(&[]).collect();
+            assert!(result.is_empty());
+        }
+    }
+
+    mod get_static_buffer_item_count {
+        use super::*;
+
+        #[test]
+        fn should_only_count_whole_items() {
+            let bytes = [0u8; 40];
+            let buffer = StaticBuffer::new(&bytes, 0);
+            assert_eq!(get_static_buffer_item_count::<FriendKey>(&buffer), 2);
+        }
+    }
+}
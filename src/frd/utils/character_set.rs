@@ -0,0 +1,31 @@
+use alloc::vec;
+use ctr::frd::TrivialCharacterSet;
+use no_std_io::Writer;
+
+/// Returns the raw id of a character set, the same value clients use to request one.
+pub fn get_character_set_id(character_set: TrivialCharacterSet) -> u8 {
+    let mut bytes = vec![];
+    bytes.checked_write_le(0, &character_set);
+    bytes.first().copied().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use no_std_io::Reader;
+
+    mod get_character_set_id {
+        use super::*;
+
+        #[test]
+        fn should_return_the_raw_character_set_id() {
+            let character_set: TrivialCharacterSet = vec![2u8].read_le(0).unwrap();
+            assert_eq!(get_character_set_id(character_set), 2);
+        }
+
+        #[test]
+        fn should_return_0_for_the_default_character_set() {
+            assert_eq!(get_character_set_id(TrivialCharacterSet::default()), 0);
+        }
+    }
+}
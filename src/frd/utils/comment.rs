@@ -0,0 +1,78 @@
+use alloc::{string::String, vec, vec::Vec};
+use ctr::frd::FriendComment;
+use no_std_io::Writer;
+
+/// The most UTF-16 code units a comment can have, not counting the null terminator.
+pub const MAX_COMMENT_CHARS: usize = 16;
+
+/// Truncates a comment to what fits in a [FriendComment],
+/// without splitting a surrogate pair.
+pub fn truncate_comment(comment: &str) -> String {
+    let mut code_unit_count = 0;
+    comment
+        .chars()
+        .take_while(|character| {
+            code_unit_count += character.len_utf16();
+            code_unit_count <= MAX_COMMENT_CHARS
+        })
+        .collect()
+}
+
+/// Encodes a comment into a null terminated [FriendComment] buffer.
+pub fn encode_comment(comment: &str) -> [u16; MAX_COMMENT_CHARS + 1] {
+    let mut comment_shorts = [0; MAX_COMMENT_CHARS + 1];
+    truncate_comment(comment)
+        .encode_utf16()
+        .enumerate()
+        .for_each(|(index, short)| {
+            comment_shorts[index] = short;
+        });
+    comment_shorts
+}
+
+pub fn decode_comment(comment: &FriendComment) -> String {
+    let mut bytes = vec![];
+    bytes.checked_write_le(0, comment);
+
+    let comment_shorts: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|short| u16::from_le_bytes([short[0], short[1]]))
+        .take(MAX_COMMENT_CHARS)
+        .take_while(|short| *short != 0)
+        .collect();
+
+    truncate_comment(&String::from_utf16_lossy(&comment_shorts))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    mod encode_comment {
+        use super::*;
+
+        #[test]
+        fn should_keep_exactly_the_first_16_code_units() {
+            let result = encode_comment("abcdefghijklmnopqrstuvwxyz");
+            let expected: Vec<u16> = "abcdefghijklmnop".encode_utf16().chain([0]).collect();
+            assert_eq!(result[..], expected[..]);
+        }
+
+        #[test]
+        fn should_not_split_a_surrogate_pair() {
+            let result = encode_comment("abcdefghijklmno😀");
+            let expected: Vec<u16> = "abcdefghijklmno".encode_utf16().chain([0, 0]).collect();
+            assert_eq!(result[..], expected[..]);
+        }
+    }
+
+    mod decode_comment {
+        use super::*;
+
+        #[test]
+        fn should_round_trip_an_encoded_comment() {
+            let comment = FriendComment::new(encode_comment("Hello"));
+            assert_eq!(decode_comment(&comment), "Hello");
+        }
+    }
+}
@@ -1,17 +1,94 @@
 use crate::frd::result::FrdErrorCode;
+use core::sync::atomic::{AtomicU32, AtomicU8, Ordering};
 
-pub fn convert_principal_id_to_friend_code(principal_id: u32) -> Result<u64, FrdErrorCode> {
-    if principal_id == 0 {
-        return Err(FrdErrorCode::InvalidPrincipalId);
+pub const FRIEND_CODE_CACHE_SIZE: usize = 128;
+
+/// A direct mapped cache of friend code checksums, so repeated conversions skip the SHA-1.
+/// Each principal id has one slot, so the cache never grows past [FRIEND_CODE_CACHE_SIZE].
+///
+/// A slot is two atomics rather than a lock.
+/// The sysmodule handles one request at a time, so a slot is never read mid write.
+struct FriendCodeCache {
+    principal_ids: [AtomicU32; FRIEND_CODE_CACHE_SIZE],
+    checksums: [AtomicU8; FRIEND_CODE_CACHE_SIZE],
+}
+
+impl FriendCodeCache {
+    // Principal id 0 is never valid, so a zeroed slot is an empty slot
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY_PRINCIPAL_ID: AtomicU32 = AtomicU32::new(0);
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY_CHECKSUM: AtomicU8 = AtomicU8::new(0);
+
+    const fn new() -> Self {
+        Self {
+            principal_ids: [Self::EMPTY_PRINCIPAL_ID; FRIEND_CODE_CACHE_SIZE],
+            checksums: [Self::EMPTY_CHECKSUM; FRIEND_CODE_CACHE_SIZE],
+        }
+    }
+
+    fn get_slot(principal_id: u32) -> usize {
+        principal_id as usize % FRIEND_CODE_CACHE_SIZE
+    }
+
+    fn get(&self, principal_id: u32) -> Option<u8> {
+        let slot = Self::get_slot(principal_id);
+
+        if self.principal_ids[slot].load(Ordering::Relaxed) == principal_id {
+            Some(self.checksums[slot].load(Ordering::Relaxed))
+        } else {
+            None
+        }
     }
 
+    fn insert(&self, principal_id: u32, checksum: u8) {
+        let slot = Self::get_slot(principal_id);
+        self.checksums[slot].store(checksum, Ordering::Relaxed);
+        self.principal_ids[slot].store(principal_id, Ordering::Relaxed);
+    }
+
+    fn get_or_calculate(&self, principal_id: u32) -> u8 {
+        if let Some(checksum) = self.get(principal_id) {
+            return checksum;
+        }
+
+        let checksum = calculate_friend_code_checksum(principal_id);
+        self.insert(principal_id, checksum);
+        checksum
+    }
+}
+
+static FRIEND_CODE_CACHE: FriendCodeCache = FriendCodeCache::new();
+
+pub const PRINCIPAL_ID_HASH_SIZE: usize = 20;
+
+/// The SHA-1 of the little endian principal id, which the friend code checksum comes from.
+pub fn calculate_principal_id_hash(principal_id: u32) -> [u8; PRINCIPAL_ID_HASH_SIZE] {
     let mut hasher = sha1::Sha1::new();
     hasher.update(&principal_id.to_le_bytes());
+    hasher.digest().bytes()
+}
+
+/// The checksum is the top 7 bits of the first hash byte.
+fn get_checksum_from_hash(hash: &[u8; PRINCIPAL_ID_HASH_SIZE]) -> u8 {
+    hash[0] >> 1
+}
 
-    let hash = hasher.digest().bytes();
-    let friend_code = (((hash[0] >> 1) as u64) << 32) | principal_id as u64;
+fn calculate_friend_code_checksum(principal_id: u32) -> u8 {
+    get_checksum_from_hash(&calculate_principal_id_hash(principal_id))
+}
 
-    Ok(friend_code)
+fn create_friend_code(principal_id: u32, checksum: u8) -> u64 {
+    ((checksum as u64) << 32) | principal_id as u64
+}
+
+pub fn convert_principal_id_to_friend_code(principal_id: u32) -> Result<u64, FrdErrorCode> {
+    if principal_id == 0 {
+        return Err(FrdErrorCode::InvalidPrincipalId);
+    }
+
+    let checksum = FRIEND_CODE_CACHE.get_or_calculate(principal_id);
+    Ok(create_friend_code(principal_id, checksum))
 }
 
 pub fn validate_friend_code(friend_code: u64) -> bool {
@@ -59,6 +136,71 @@ mod test {
         }
     }
 
+    mod calculate_principal_id_hash {
+        use super::*;
+
+        const PRINCIPAL_ID: u32 = 0xaabbccdd;
+
+        // The SHA-1 of the bytes dd cc bb aa, computed outside this crate
+        const PRINCIPAL_ID_HASH: [u8; PRINCIPAL_ID_HASH_SIZE] = [
+            0x71, 0xc8, 0x01, 0x42, 0x5f, 0xa2, 0xf6, 0x6d, 0x9c, 0x23, 0x6d, 0xde, 0x9b, 0xe5,
+            0x31, 0xec, 0x8e, 0xae, 0x9d, 0xbc,
+        ];
+
+        #[test]
+        fn should_hash_the_little_endian_principal_id() {
+            let hash = calculate_principal_id_hash(PRINCIPAL_ID);
+            assert_eq!(hash, PRINCIPAL_ID_HASH);
+        }
+
+        #[test]
+        fn should_take_the_checksum_from_the_top_bits_of_the_first_byte() {
+            let checksum = get_checksum_from_hash(&PRINCIPAL_ID_HASH);
+            assert_eq!(checksum, 0x71 >> 1);
+        }
+    }
+
+    mod friend_code_cache {
+        use super::*;
+
+        #[test]
+        fn should_match_the_uncached_friend_code() {
+            let cache = FriendCodeCache::new();
+
+            let uncached_checksum = cache.get_or_calculate(0xaabbccdd);
+            let cached_checksum = cache.get_or_calculate(0xaabbccdd);
+
+            assert_eq!(cache.get(0xaabbccdd), Some(uncached_checksum));
+            assert_eq!(cached_checksum, uncached_checksum);
+            assert_eq!(
+                create_friend_code(0xaabbccdd, cached_checksum),
+                0x38aabbccdd
+            );
+        }
+
+        #[test]
+        fn should_replace_an_entry_in_the_same_slot() {
+            let cache = FriendCodeCache::new();
+            let principal_id = 0xaabbccdd;
+            let colliding_principal_id = principal_id - FRIEND_CODE_CACHE_SIZE as u32;
+
+            cache.get_or_calculate(principal_id);
+            cache.get_or_calculate(colliding_principal_id);
+
+            assert_eq!(cache.get(principal_id), None);
+            assert_eq!(
+                cache.get(colliding_principal_id),
+                Some(calculate_friend_code_checksum(colliding_principal_id))
+            );
+        }
+
+        #[test]
+        fn should_not_have_entries_when_empty() {
+            let cache = FriendCodeCache::new();
+            assert_eq!(cache.get(1), None);
+        }
+    }
+
     mod test_validate_friend_code {
         use super::*;
 
@@ -79,6 +221,18 @@ mod test {
             let is_valid = validate_friend_code(0);
             assert_eq!(is_valid, false);
         }
+
+        #[test]
+        fn should_return_false_if_bits_above_the_checksum_are_set() {
+            let is_valid = validate_friend_code(0x8038aabbccdd);
+            assert_eq!(is_valid, false);
+        }
+
+        #[test]
+        fn should_return_false_if_the_top_bit_is_set() {
+            let is_valid = validate_friend_code(0x80000038aabbccdd);
+            assert_eq!(is_valid, false);
+        }
     }
 
     mod test_convert_friend_code_to_principal_id {
@@ -0,0 +1,55 @@
+use ctr::frd::FriendKey;
+
+pub trait FriendKeyMatch {
+    /// Compares the principal id and local friend code, ignoring the padding,
+    /// since clients don't always initialize it.
+    fn matches(&self, other: &FriendKey) -> bool;
+}
+
+impl FriendKeyMatch for FriendKey {
+    fn matches(&self, other: &FriendKey) -> bool {
+        self.principal_id == other.principal_id && self.local_friend_code == other.local_friend_code
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    mod matches {
+        use super::*;
+
+        const FRIEND_KEY: FriendKey = FriendKey {
+            principal_id: 0x11223344,
+            padding: 0,
+            local_friend_code: 0x0011223344,
+        };
+
+        #[test]
+        fn should_ignore_the_padding() {
+            let other = FriendKey {
+                padding: 0xdeadbeef,
+                ..FRIEND_KEY
+            };
+            assert!(FRIEND_KEY.matches(&other));
+        }
+
+        #[test]
+        fn should_not_match_a_different_principal_id() {
+            let other = FriendKey {
+                principal_id: 0x55667788,
+                ..FRIEND_KEY
+            };
+            assert!(!FRIEND_KEY.matches(&other));
+        }
+
+        #[test]
+        fn should_not_match_a_different_local_friend_code() {
+            let other = FriendKey {
+                local_friend_code: 0x0055667788,
+                ..FRIEND_KEY
+            };
+            assert!(!FRIEND_KEY.matches(&other));
+        }
+    }
+}
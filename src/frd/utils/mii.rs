@@ -0,0 +1,77 @@
+use alloc::vec;
+use ctr::frd::Mii;
+use no_std_io::Writer;
+
+pub const MII_DATA_SIZE: usize = 0x60;
+const MII_CRC_OFFSET: usize = MII_DATA_SIZE - 2;
+
+/// CRC-16/XMODEM, which is what the 3ds uses to checksum Mii data.
+fn calculate_mii_crc(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |crc, byte| {
+        (0..8).fold(crc ^ ((*byte as u16) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+/// Checks the big endian CRC stored in the last two bytes of a Mii.
+pub fn is_valid_mii(mii: &Mii) -> bool {
+    let mut bytes = vec![];
+    bytes.checked_write_le(0, mii);
+
+    if bytes.len() != MII_DATA_SIZE {
+        return false;
+    }
+
+    let stored_crc = u16::from_be_bytes([bytes[MII_CRC_OFFSET], bytes[MII_CRC_OFFSET + 1]]);
+    calculate_mii_crc(&bytes[..MII_CRC_OFFSET]) == stored_crc
+}
+
+#[cfg(test)]
+pub(crate) fn create_mii_bytes() -> [u8; MII_DATA_SIZE] {
+    let mut bytes = [0; MII_DATA_SIZE];
+    bytes[..MII_CRC_OFFSET]
+        .iter_mut()
+        .enumerate()
+        .for_each(|(index, byte)| *byte = index as u8);
+    let crc = calculate_mii_crc(&bytes[..MII_CRC_OFFSET]);
+    bytes[MII_CRC_OFFSET..].copy_from_slice(&crc.to_be_bytes());
+    bytes
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    mod calculate_mii_crc {
+        use super::*;
+
+        #[test]
+        fn should_match_the_xmodem_check_value() {
+            assert_eq!(calculate_mii_crc(b"123456789"), 0x31c3);
+        }
+    }
+
+    mod is_valid_mii {
+        use super::*;
+
+        #[test]
+        fn should_accept_a_mii_with_a_matching_crc() {
+            let mii = Mii::new(create_mii_bytes());
+            assert!(is_valid_mii(&mii));
+        }
+
+        #[test]
+        fn should_reject_a_corrupt_mii() {
+            let mut bytes = create_mii_bytes();
+            bytes[0x10] ^= 0xff;
+
+            let mii = Mii::new(bytes);
+            assert!(!is_valid_mii(&mii));
+        }
+    }
+}
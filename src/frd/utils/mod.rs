@@ -1,2 +1,23 @@
+mod buffer;
+pub use buffer::*;
+
+mod character_set;
+pub use character_set::*;
+
+mod comment;
+pub use comment::*;
+
 mod friend_code;
 pub use friend_code::*;
+
+mod friend_key;
+pub use friend_key::*;
+
+mod mii;
+pub use mii::*;
+
+mod stream;
+pub use stream::*;
+
+mod time;
+pub use time::*;
@@ -0,0 +1,100 @@
+use crate::frd::save::friend_list::MAX_FRIEND_COUNT;
+use core::{cmp::min, mem};
+use no_std_io::{Cursor, EndianWrite, StreamWriter};
+
+/// Returns how many `T`s to write into a client buffer.
+///
+/// This is the requested count, limited by the friend count and by how many `T`s fit in the buffer.
+pub fn get_max_out_count<T>(requested_count: u32, buffer_len: usize) -> usize {
+    let max_out_count = min(requested_count as usize, MAX_FRIEND_COUNT);
+    min(max_out_count, buffer_len / mem::size_of::<T>())
+}
+
+/// Writes each item to the stream and returns the number of bytes actually written.
+///
+/// Writes that don't fit in the stream are skipped, so the returned length
+/// can be smaller than the size of all items.
+pub fn write_items_le<S, T, I>(stream: &mut S, items: I) -> usize
+where
+    S: StreamWriter + Cursor,
+    T: EndianWrite,
+    I: IntoIterator<Item = T>,
+{
+    let start_index = stream.get_index();
+
+    for item in items {
+        stream.checked_write_stream_le(&item);
+    }
+
+    stream.get_index() - start_index
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ctr::frd::{Mii, NotificationEvent};
+    use no_std_io::StreamContainer;
+
+    mod get_max_out_count {
+        use super::*;
+
+        #[test]
+        fn should_return_the_requested_count_when_the_buffer_is_large_enough() {
+            let result = get_max_out_count::<Mii>(2, mem::size_of::<Mii>() * 4);
+            assert_eq!(result, 2);
+        }
+
+        #[test]
+        fn should_clamp_the_count_to_the_buffer_size() {
+            let result = get_max_out_count::<Mii>(10, mem::size_of::<Mii>() * 3 + 1);
+            assert_eq!(result, 3);
+        }
+
+        #[test]
+        fn should_clamp_the_count_to_the_max_friend_count() {
+            let result = get_max_out_count::<u8>(1000, 1000);
+            assert_eq!(result, MAX_FRIEND_COUNT);
+        }
+
+        #[test]
+        fn should_clamp_notification_events_to_a_smaller_buffer() {
+            let buffer_len = mem::size_of::<NotificationEvent>() * 2;
+            let result = get_max_out_count::<NotificationEvent>(50, buffer_len);
+            assert_eq!(result, 2);
+        }
+
+        #[test]
+        fn should_return_0_for_an_empty_buffer() {
+            let result = get_max_out_count::<Mii>(10, 0);
+            assert_eq!(result, 0);
+        }
+    }
+
+    mod write_items_le {
+        use super::*;
+
+        #[test]
+        fn should_return_the_number_of_bytes_written() {
+            let mut buffer = [0u8; 0x100];
+            let mut stream = StreamContainer::new(&mut buffer[..]);
+            let written_len = write_items_le(&mut stream, [Mii::default(), Mii::default()]);
+            assert_eq!(written_len, 0xc0);
+        }
+
+        #[test]
+        fn should_only_count_items_that_fit() {
+            let mut buffer = [0u8; 100];
+            let mut stream = StreamContainer::new(&mut buffer[..]);
+            let written_len = write_items_le(&mut stream, [Mii::default(), Mii::default()]);
+            assert_eq!(written_len, 0x60);
+        }
+
+        #[test]
+        fn should_return_0_for_an_empty_buffer() {
+            let mut buffer = [0u8; 0];
+            let mut stream = StreamContainer::new(&mut buffer[..]);
+            let written_len = write_items_le(&mut stream, [Mii::default()]);
+            assert_eq!(written_len, 0);
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use alloc::{format, string::String};
+use ctr::{
+    os::get_time,
+    time::{FormattedTimestamp, SystemTimestamp},
+};
+
+/// Converts a timestamp into the format friend list entries store.
+pub fn format_timestamp(timestamp: SystemTimestamp) -> FormattedTimestamp {
+    let year_month_date = timestamp.get_year_month_date();
+    FormattedTimestamp::new(
+        year_month_date.year as u16,
+        year_month_date.month as u16,
+        year_month_date.date as u16,
+        timestamp.get_hours() as u16,
+        timestamp.get_minutes() as u16,
+        timestamp.get_seconds() as u16,
+    )
+}
+
+pub fn get_current_formatted_timestamp() -> FormattedTimestamp {
+    format_timestamp(SystemTimestamp::new(get_time()))
+}
+
+/// Formats a timestamp as `YYYY-MM-DD HH:MM:SS` for log lines.
+pub fn format_timestamp_for_log(timestamp: SystemTimestamp) -> String {
+    let year_month_date = timestamp.get_year_month_date();
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year_month_date.year,
+        year_month_date.month,
+        year_month_date.date,
+        timestamp.get_hours(),
+        timestamp.get_minutes(),
+        timestamp.get_seconds()
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    mod format_timestamp {
+        use super::*;
+
+        #[test]
+        fn should_round_trip_a_formatted_timestamp() {
+            let formatted_timestamp = FormattedTimestamp::new(2021, 1, 2, 3, 4, 5);
+            let result = format_timestamp(formatted_timestamp.into());
+            assert_eq!(result, formatted_timestamp);
+        }
+    }
+
+    mod format_timestamp_for_log {
+        use super::*;
+
+        #[test]
+        fn should_zero_pad_each_field() {
+            let timestamp = FormattedTimestamp::new(2021, 1, 2, 3, 4, 5).into();
+            let result = format_timestamp_for_log(timestamp);
+            assert_eq!(result, "2021-01-02 03:04:05");
+        }
+    }
+}
@@ -1,6 +1,15 @@
 use super::WiFiConnectionStatus;
-use crate::frd::context::FriendServiceContext;
-use ctr::{ac::AcController, result::CtrResult, svc};
+use crate::{
+    frd::{context::FriendServiceContext, result::FrdErrorCode},
+    log,
+};
+use alloc::format;
+use ctr::{
+    ac::AcController,
+    result::{CtrResult, ResultCode},
+    svc,
+};
+use no_std_io::{EndianRead, EndianWrite};
 
 pub fn get_wifi_state(ndm_wifi_state: u8, wifi_connection_status: WiFiConnectionStatus) -> u32 {
     match (ndm_wifi_state, wifi_connection_status) {
@@ -16,47 +25,181 @@ pub fn get_wifi_state(ndm_wifi_state: u8, wifi_connection_status: WiFiConnection
     }
 }
 
+/// The unmapped state behind [get_wifi_state], for debugging connection issues.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EndianRead, EndianWrite)]
+pub struct WiFiStateDetail {
+    pub ndm_wifi_state: u32,
+    pub wifi_connection_status: u32,
+}
+
+pub fn get_wifi_state_detail(
+    ndm_wifi_state: u8,
+    wifi_connection_status: WiFiConnectionStatus,
+) -> WiFiStateDetail {
+    WiFiStateDetail {
+        ndm_wifi_state: ndm_wifi_state as u32,
+        wifi_connection_status: wifi_connection_status as u32,
+    }
+}
+
+/// Records the wifi state before the first change of a request, for [take_wifi_state_change].
+/// The NDM state is part of the wifi state, so this needs to happen before either changes.
+pub fn track_wifi_state_change(context: &mut FriendServiceContext) {
+    let old_state = get_wifi_state(context.ndm_wifi_state, context.wifi_connection_status);
+    context.wifi_state_before_change.get_or_insert(old_state);
+}
+
+/// Changes the connection status without signaling.
+/// [signal_wifi_state_change] signals once the request is done, if the state actually changed.
 pub fn set_wifi_connection_status(
     context: &mut FriendServiceContext,
     next_wifi_connection_status: WiFiConnectionStatus,
-) -> CtrResult<()> {
+) {
     if context.wifi_connection_status != next_wifi_connection_status {
-        let old_state = get_wifi_state(context.ndm_wifi_state, context.wifi_connection_status);
+        track_wifi_state_change(context);
         context.wifi_connection_status = next_wifi_connection_status;
-        let new_state = get_wifi_state(context.ndm_wifi_state, context.wifi_connection_status);
+    }
+}
 
-        if old_state != new_state {
-            svc::signal_event(&context.ndm_wifi_event_handle)?;
+/// Returns whether the wifi state is different than before the first status change,
+/// and starts tracking changes over again.
+///
+/// A state that changes and changes back, like a connect that immediately fails,
+/// doesn't count, so clients aren't woken up for nothing.
+pub fn take_wifi_state_change(context: &mut FriendServiceContext) -> bool {
+    match context.wifi_state_before_change.take() {
+        Some(old_state) => {
+            old_state != get_wifi_state(context.ndm_wifi_state, context.wifi_connection_status)
         }
+        None => false,
+    }
+}
+
+pub fn signal_wifi_state_change(context: &mut FriendServiceContext) -> CtrResult<()> {
+    if take_wifi_state_change(context) {
+        svc::signal_event(&context.ndm_wifi_event_handle)?;
     }
 
     Ok(())
 }
 
-pub fn connect_to_wifi(context: &mut FriendServiceContext) -> CtrResult<()> {
+/// The OS can drop the connection without a disconnect request, which would leave us reporting connected.
+/// Connections in progress are left alone, since they're still being handled by a request.
+pub fn get_reconciled_wifi_connection_status(
+    wifi_connection_status: WiFiConnectionStatus,
+    is_connected: bool,
+) -> WiFiConnectionStatus {
+    match (wifi_connection_status, is_connected) {
+        (WiFiConnectionStatus::Connected, false) => WiFiConnectionStatus::Idle,
+        (wifi_connection_status, _) => wifi_connection_status,
+    }
+}
+
+/// Updates the connection status to match what AC reports.
+/// Like [set_wifi_connection_status], this doesn't signal.
+pub fn reconcile_wifi_connection_status(context: &mut FriendServiceContext, is_connected: bool) {
+    let next_wifi_connection_status =
+        get_reconciled_wifi_connection_status(context.wifi_connection_status, is_connected);
+    set_wifi_connection_status(context, next_wifi_connection_status);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectAction {
+    Connect,
+    /// Already connected, so NDM only needs to be told.
+    SignalConnected,
+    /// A connection is in progress, so only the NDM state changes.
+    WaitForConnection,
+}
+
+/// Decides what a connect request should do, or returns an error if it can't happen right now.
+pub fn get_connect_action(
+    wifi_connection_status: WiFiConnectionStatus,
+) -> Result<ConnectAction, FrdErrorCode> {
+    match wifi_connection_status {
+        WiFiConnectionStatus::Idle => Ok(ConnectAction::Connect),
+        WiFiConnectionStatus::Connected => Ok(ConnectAction::SignalConnected),
+        WiFiConnectionStatus::Connecting => Ok(ConnectAction::WaitForConnection),
+        WiFiConnectionStatus::Disconnecting => Err(FrdErrorCode::WiFiBusy),
+    }
+}
+
+/// Puts NDM back in the state it was in before a connect attempt that failed.
+/// AC's result is logged, since clients only get [FrdErrorCode::WiFiConnectionFailed].
+fn roll_back_connect(
+    context: &mut FriendServiceContext,
+    original_ndm_wifi_state: u8,
+    ac_result_code: ResultCode,
+) -> FrdErrorCode {
+    log::debug(&format!("Failed to connect to wifi: {:?}", ac_result_code));
+    context.ndm_wifi_state = original_ndm_wifi_state;
+    FrdErrorCode::WiFiConnectionFailed
+}
+
+/// Starts a connect request, returning what it should do along with the NDM state from before it.
+pub fn start_connect(
+    context: &mut FriendServiceContext,
+) -> Result<(ConnectAction, u8), FrdErrorCode> {
+    let connect_action = get_connect_action(context.wifi_connection_status)?;
     let original_ndm_wifi_state = context.ndm_wifi_state;
+
+    if connect_action == ConnectAction::Connect {
+        track_wifi_state_change(context);
+    }
+
     context.ndm_wifi_state = 2;
+    Ok((connect_action, original_ndm_wifi_state))
+}
 
-    if context.wifi_connection_status == WiFiConnectionStatus::Idle {
-        set_wifi_connection_status(context, WiFiConnectionStatus::Connecting)?;
+/// Updates the connection status with the result of a connect, without signaling.
+pub fn finish_connect<T>(
+    context: &mut FriendServiceContext,
+    original_ndm_wifi_state: u8,
+    connect_result: CtrResult<T>,
+) -> Result<(), FrdErrorCode> {
+    match connect_result {
+        Ok(_) => {
+            set_wifi_connection_status(context, WiFiConnectionStatus::Connected);
+            Ok(())
+        }
+        Err(ac_result_code) => {
+            let error_code = roll_back_connect(context, original_ndm_wifi_state, ac_result_code);
+            set_wifi_connection_status(context, WiFiConnectionStatus::Idle);
+            Err(error_code)
+        }
+    }
+}
 
-        return match AcController::quick_connect() {
-            Ok(_) => {
-                set_wifi_connection_status(context, WiFiConnectionStatus::Connected)?;
-                Ok(())
-            }
-            Err(result_code) => {
-                set_wifi_connection_status(context, WiFiConnectionStatus::Idle)?;
-                Err(result_code)
+pub fn connect_to_wifi(context: &mut FriendServiceContext) -> CtrResult<()> {
+    connect_to_wifi_with(context, AcController::quick_connect)
+}
+
+/// [connect_to_wifi] with the connect call passed in, so a failed connect can be tested on the host.
+fn connect_to_wifi_with<T>(
+    context: &mut FriendServiceContext,
+    quick_connect: impl FnOnce() -> CtrResult<T>,
+) -> CtrResult<()> {
+    let (connect_action, original_ndm_wifi_state) = start_connect(context)?;
+
+    match connect_action {
+        ConnectAction::Connect => {
+            set_wifi_connection_status(context, WiFiConnectionStatus::Connecting);
+            let connect_result = finish_connect(context, original_ndm_wifi_state, quick_connect());
+            signal_wifi_state_change(context)?;
+            connect_result.map_err(|error_code| error_code.into())
+        }
+        ConnectAction::SignalConnected => {
+            svc::signal_event(&context.ndm_wifi_event_handle)?;
+            Ok(())
+        }
+        ConnectAction::WaitForConnection => {
+            if original_ndm_wifi_state != 2 {
+                svc::signal_event(&context.ndm_wifi_event_handle)?;
             }
-        };
-    }
 
-    if original_ndm_wifi_state != 2 {
-        svc::signal_event(&context.ndm_wifi_event_handle)?;
+            Ok(())
+        }
     }
-
-    Ok(())
 }
 
 #[cfg(test)]
@@ -141,4 +284,254 @@ mod test {
             assert_eq!(result, 1);
         }
     }
+
+    mod get_connect_action {
+        use super::*;
+
+        #[test]
+        fn should_connect_when_idle() {
+            let result = get_connect_action(WiFiConnectionStatus::Idle);
+            assert_eq!(result, Ok(ConnectAction::Connect));
+        }
+
+        #[test]
+        fn should_only_signal_when_already_connected() {
+            let result = get_connect_action(WiFiConnectionStatus::Connected);
+            assert_eq!(result, Ok(ConnectAction::SignalConnected));
+        }
+
+        #[test]
+        fn should_wait_when_connecting() {
+            let result = get_connect_action(WiFiConnectionStatus::Connecting);
+            assert_eq!(result, Ok(ConnectAction::WaitForConnection));
+        }
+
+        #[test]
+        fn should_return_a_busy_error_when_disconnecting() {
+            let result = get_connect_action(WiFiConnectionStatus::Disconnecting);
+            assert_eq!(result, Err(FrdErrorCode::WiFiBusy));
+        }
+    }
+
+    mod connect_to_wifi {
+        use super::*;
+
+        #[test]
+        fn should_not_change_the_ndm_state_when_disconnecting() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.ndm_wifi_state = 1;
+            context.wifi_connection_status = WiFiConnectionStatus::Disconnecting;
+
+            let result = connect_to_wifi(&mut context);
+
+            assert_eq!(result, Err(FrdErrorCode::WiFiBusy.into_result_code()));
+            assert_eq!(context.ndm_wifi_state, 1);
+            assert_eq!(
+                context.wifi_connection_status,
+                WiFiConnectionStatus::Disconnecting
+            );
+        }
+
+        #[test]
+        fn should_roll_back_a_failed_connect() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.ndm_wifi_state = 1;
+
+            let result = connect_to_wifi_with::<()>(&mut context, || {
+                Err(ctr::result::error::invalid_value())
+            });
+
+            assert_eq!(
+                result,
+                Err(FrdErrorCode::WiFiConnectionFailed.into_result_code())
+            );
+            assert_eq!(context.ndm_wifi_state, 1);
+            assert_eq!(context.wifi_connection_status, WiFiConnectionStatus::Idle);
+            assert_eq!(context.wifi_state_before_change, None);
+        }
+    }
+
+    mod finish_connect {
+        use super::*;
+        use ctr::result::error;
+
+        #[test]
+        fn should_report_a_successful_connect_from_ndm_state_0() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.ndm_wifi_state = 0;
+
+            let (_, original_ndm_wifi_state) = start_connect(&mut context).unwrap();
+            set_wifi_connection_status(&mut context, WiFiConnectionStatus::Connecting);
+            let result = finish_connect(&mut context, original_ndm_wifi_state, Ok(()));
+
+            assert_eq!(result, Ok(()));
+            assert!(take_wifi_state_change(&mut context));
+        }
+
+        #[test]
+        fn should_not_report_a_failed_connect_from_ndm_state_0() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.ndm_wifi_state = 0;
+
+            let (_, original_ndm_wifi_state) = start_connect(&mut context).unwrap();
+            set_wifi_connection_status(&mut context, WiFiConnectionStatus::Connecting);
+            let result = finish_connect::<()>(
+                &mut context,
+                original_ndm_wifi_state,
+                Err(error::invalid_value()),
+            );
+
+            assert_eq!(result, Err(FrdErrorCode::WiFiConnectionFailed));
+            assert_eq!(context.ndm_wifi_state, 0);
+            assert_eq!(context.wifi_connection_status, WiFiConnectionStatus::Idle);
+            assert!(!take_wifi_state_change(&mut context));
+        }
+    }
+
+    mod take_wifi_state_change {
+        use super::*;
+
+        #[test]
+        fn should_report_a_change() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.ndm_wifi_state = 2;
+
+            set_wifi_connection_status(&mut context, WiFiConnectionStatus::Connecting);
+            set_wifi_connection_status(&mut context, WiFiConnectionStatus::Connected);
+
+            assert!(take_wifi_state_change(&mut context));
+            assert!(!take_wifi_state_change(&mut context));
+        }
+
+        #[test]
+        fn should_not_report_a_connect_followed_by_a_disconnect() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.ndm_wifi_state = 2;
+
+            set_wifi_connection_status(&mut context, WiFiConnectionStatus::Connecting);
+            set_wifi_connection_status(&mut context, WiFiConnectionStatus::Connected);
+            set_wifi_connection_status(&mut context, WiFiConnectionStatus::Disconnecting);
+            set_wifi_connection_status(&mut context, WiFiConnectionStatus::Idle);
+
+            assert!(!take_wifi_state_change(&mut context));
+        }
+
+        #[test]
+        fn should_report_one_change_per_request() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.ndm_wifi_state = 2;
+
+            set_wifi_connection_status(&mut context, WiFiConnectionStatus::Connecting);
+            set_wifi_connection_status(&mut context, WiFiConnectionStatus::Connected);
+            let connect_changed = take_wifi_state_change(&mut context);
+
+            set_wifi_connection_status(&mut context, WiFiConnectionStatus::Disconnecting);
+            set_wifi_connection_status(&mut context, WiFiConnectionStatus::Idle);
+            let disconnect_changed = take_wifi_state_change(&mut context);
+
+            assert!(connect_changed);
+            assert!(disconnect_changed);
+        }
+
+        #[test]
+        fn should_not_report_without_a_status_change() {
+            let mut context = FriendServiceContext::new().unwrap();
+            assert!(!take_wifi_state_change(&mut context));
+        }
+    }
+
+    mod get_reconciled_wifi_connection_status {
+        use super::*;
+
+        #[test]
+        fn should_return_idle_when_the_connection_was_dropped() {
+            let result =
+                get_reconciled_wifi_connection_status(WiFiConnectionStatus::Connected, false);
+            assert_eq!(result, WiFiConnectionStatus::Idle);
+        }
+
+        #[test]
+        fn should_stay_connected_when_still_connected() {
+            let result =
+                get_reconciled_wifi_connection_status(WiFiConnectionStatus::Connected, true);
+            assert_eq!(result, WiFiConnectionStatus::Connected);
+        }
+
+        #[test]
+        fn should_not_change_a_connection_in_progress() {
+            let result =
+                get_reconciled_wifi_connection_status(WiFiConnectionStatus::Connecting, false);
+            assert_eq!(result, WiFiConnectionStatus::Connecting);
+        }
+    }
+
+    mod reconcile_wifi_connection_status {
+        use super::*;
+
+        #[test]
+        fn should_update_a_stale_connected_status() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.ndm_wifi_state = 2;
+            context.wifi_connection_status = WiFiConnectionStatus::Connected;
+
+            reconcile_wifi_connection_status(&mut context, false);
+
+            assert_eq!(context.wifi_connection_status, WiFiConnectionStatus::Idle);
+            assert!(take_wifi_state_change(&mut context));
+        }
+
+        #[test]
+        fn should_not_report_a_change_when_the_status_is_accurate() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.wifi_connection_status = WiFiConnectionStatus::Connected;
+
+            reconcile_wifi_connection_status(&mut context, true);
+
+            assert_eq!(
+                context.wifi_connection_status,
+                WiFiConnectionStatus::Connected
+            );
+            assert!(!take_wifi_state_change(&mut context));
+        }
+    }
+
+    mod get_wifi_state_detail {
+        use super::*;
+
+        #[test]
+        fn should_return_the_raw_state() {
+            let result = get_wifi_state_detail(1, WiFiConnectionStatus::Disconnecting);
+            assert_eq!(
+                result,
+                WiFiStateDetail {
+                    ndm_wifi_state: 1,
+                    wifi_connection_status: 3,
+                }
+            );
+        }
+
+        #[test]
+        fn should_not_map_values_like_get_wifi_state() {
+            let result = get_wifi_state_detail(2, WiFiConnectionStatus::Idle);
+            assert_eq!(result.ndm_wifi_state, 2);
+            assert_eq!(result.wifi_connection_status, 0);
+            assert_eq!(get_wifi_state(2, WiFiConnectionStatus::Idle), 1);
+        }
+    }
+
+    mod roll_back_connect {
+        use super::*;
+        use ctr::result::error;
+
+        #[test]
+        fn should_restore_the_original_ndm_state() {
+            let mut context = FriendServiceContext::new().unwrap();
+            context.ndm_wifi_state = 2;
+
+            let error_code = roll_back_connect(&mut context, 1, error::invalid_value());
+
+            assert_eq!(error_code, FrdErrorCode::WiFiConnectionFailed);
+            assert_eq!(context.ndm_wifi_state, 1);
+        }
+    }
 }
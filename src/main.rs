@@ -25,12 +25,16 @@ use ctr::{
     },
 };
 use frd::{
-    context::FriendServiceContext, frda::FrdACommand, frdn::FrdNCommand, frdu::FrdUCommand,
+    context::FriendServiceContext, frda::FrdACommand, frdn, frdn::FrdNCommand, frdu::FrdUCommand,
     notification::handle_sleep_notification,
 };
 
+/// The memory handed to HTTPC for request and response data.
+/// This needs to be page aligned, and a multiple of the page size.
+const HTTP_BUFFER_SIZE: usize = 0x2000;
+
 #[repr(align(0x1000))]
-struct HttpBuffer([u8; 0x1000]);
+struct HttpBuffer([u8; HTTP_BUFFER_SIZE]);
 
 impl HttpBuffer {
     fn as_mut_slice(&mut self) -> &mut [u8] {
@@ -38,9 +42,11 @@ impl HttpBuffer {
     }
 }
 
-static mut HTTP_BUFFER: HttpBuffer = HttpBuffer([0; 0x1000]);
+static mut HTTP_BUFFER: HttpBuffer = HttpBuffer([0; HTTP_BUFFER_SIZE]);
 
 fn handle_termination_notification(_notification: u32) -> CtrResult {
+    // Notifications are only handled between requests, after the last reply was sent,
+    // so exiting here can't leave a client waiting on a reply
     svc::exit_process();
 }
 
@@ -54,10 +60,8 @@ impl FriendSysmodule {
             context: FriendServiceContext::new().unwrap(),
         }
     }
-}
 
-impl ServiceRouter for FriendSysmodule {
-    fn handle_request(
+    fn route_request(
         &mut self,
         service_id: usize,
         session_index: usize,
@@ -70,6 +74,7 @@ impl ServiceRouter for FriendSysmodule {
             FrdNCommand::ConnectToWiFi,
             FrdNCommand::DisconnectFromWiFi,
             FrdNCommand::GetWiFiState,
+            FrdNCommand::GetWiFiStateDetail,
             FrdACommand::HasLoggedIn,
             FrdACommand::IsOnline,
             FrdACommand::Login,
@@ -124,10 +129,28 @@ impl ServiceRouter for FriendSysmodule {
             FrdACommand::AddFriendWithApproach,
             FrdACommand::DecryptApproachContext,
             FrdACommand::GetExtendedNatProperties,
+            FrdACommand::GetFriendLastOnline,
+            FrdACommand::GetFriendListCount,
+            FrdACommand::GetMyPackedPreference,
+            FrdACommand::ClearEventNotifications,
+            FrdACommand::GetFriendInfoPage,
+            FrdACommand::HasDetectedNatProperties,
+            FrdACommand::GetServiceLocatorPort,
             FrdACommand::CreateLocalAccount,
             FrdACommand::HasUserData,
+            FrdACommand::AddFriendOnline,
             FrdACommand::SetPresenseGameKey,
+            FrdACommand::SetMyFavoriteGame,
             FrdACommand::SetMyData,
+            FrdACommand::SetPersonalComment,
+            FrdACommand::IncrementAccountConfigCounter,
+            FrdACommand::SetFriendOnline,
+            FrdACommand::ReloadFriendList,
+            FrdACommand::SetMyPresence,
+            FrdACommand::GetSessionClientInfo,
+            FrdACommand::GetAccountConfigCounter,
+            FrdACommand::GetNascEnvironment,
+            FrdACommand::SetNascAttemptCount,
             FrdUCommand::HasLoggedIn,
             FrdUCommand::IsOnline,
             FrdUCommand::Login,
@@ -182,11 +205,32 @@ impl ServiceRouter for FriendSysmodule {
             FrdUCommand::AddFriendWithApproach,
             FrdUCommand::DecryptApproachContext,
             FrdUCommand::GetExtendedNatProperties,
+            FrdUCommand::GetFriendLastOnline,
+            FrdUCommand::GetFriendListCount,
+            FrdUCommand::GetMyPackedPreference,
+            FrdUCommand::ClearEventNotifications,
+            FrdUCommand::GetFriendInfoPage,
+            FrdUCommand::HasDetectedNatProperties,
+            FrdUCommand::GetServiceLocatorPort,
         )
     }
+}
+
+impl ServiceRouter for FriendSysmodule {
+    fn handle_request(
+        &mut self,
+        service_id: usize,
+        session_index: usize,
+    ) -> CtrResult<WrittenCommand> {
+        if service_id == FrdNCommand::ID && frdn::refresh_wifi_connection_status(self).is_err() {
+            log::debug("Failed to refresh the wifi connection status");
+        }
+
+        self.route_request(service_id, session_index)
+    }
 
-    fn accept_session(&mut self, _session_index: usize) {
-        self.context.accept_session()
+    fn accept_session(&mut self, session_index: usize) {
+        self.context.accept_session(session_index)
     }
 
     fn close_session(&mut self, session_index: usize) {